@@ -0,0 +1,210 @@
+// Storage backends behind `HdfsFileSystem`.
+//
+// `LibHdfsBackend` talks to a real cluster through libhdfs; `LocalBackend`
+// serves a local directory for development; `MemoryBackend` keeps everything
+// in process so the file and listing logic can be tested without a JVM or a
+// namenode.
+
+use crate::{HdfsError, HdfsErrorKind, HedgedReadMetrics, ReadStatistics};
+use crate::error::Result;
+
+pub mod libhdfs;
+pub mod local;
+pub mod memory;
+
+pub use self::libhdfs::LibHdfsBackend;
+pub use self::local::LocalBackend;
+pub use self::memory::MemoryBackend;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    File,
+    Directory,
+}
+
+// An owned copy of libhdfs' `hdfsFileInfo`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileInfo {
+    pub kind: ObjectKind,
+    // the fully qualified name, e.g. `hdfs://nn:8020/user/etl/part-0000`
+    pub name: String,
+    // seconds since the epoch
+    pub last_mod: i64,
+    pub size: i64,
+    pub replication: i16,
+    pub block_size: i64,
+    pub owner: String,
+    pub group: String,
+    pub permissions: i16,
+    // seconds since the epoch
+    pub last_access: i64,
+}
+
+// How to open a file, as passed to `hdfsOpenFile`. Zero sizes and
+// replication mean "use the configured default".
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpenRequest {
+    // `O_RDONLY`, `O_WRONLY` (create or overwrite) or `O_WRONLY | O_APPEND`
+    pub flags: i32,
+    pub buffer_size: i32,
+    // only used when creating a file
+    pub replication: i16,
+    // only used when creating a file; may exceed 2 GiB
+    pub block_size: i64,
+}
+
+impl OpenRequest {
+
+    pub fn new(flags: i32) -> OpenRequest {
+        OpenRequest { flags, ..OpenRequest::default() }
+    }
+}
+
+
+// The filesystem operations `HdfsFileSystem` is built on.
+//
+// Paths are handed over exactly as the caller gave them, already checked to
+// be valid UTF-8 without NUL bytes.
+pub trait FileSystemBackend: Send + Sync {
+
+    fn exists(&self, path: &str) -> Result<bool>;
+
+    fn path_info(&self, path: &str) -> Result<FileInfo>;
+
+    fn open(&self, path: &str, request: &OpenRequest) -> Result<Box<dyn BackendFile>>;
+
+    fn list(&self, path: &str) -> Result<Vec<FileInfo>>;
+
+    fn delete(&self, path: &str, recursive: bool) -> Result<()>;
+
+    // Creates a directory and its missing parents; existing directories are
+    // left alone.
+    fn create_dir(&self, path: &str) -> Result<()>;
+
+    // Moves `from` to `to`, which does not exist yet.
+    fn rename(&self, from: &str, to: &str) -> Result<()>;
+
+    fn set_permissions(&self, path: &str, mode: i16) -> Result<()>;
+
+    // `None` leaves the owner or group unchanged.
+    fn set_owner(&self, path: &str, owner: Option<&str>, group: Option<&str>) -> Result<()>;
+
+    // Seconds since the epoch; `None` leaves the time unchanged.
+    fn set_times(&self, path: &str, last_mod: Option<i64>, last_access: Option<i64>) -> Result<()>;
+
+    fn set_replication(&self, path: &str, replication: i16) -> Result<()>;
+
+    // The hosts serving each block overlapping `[start, start + length)`.
+    fn hosts(&self, path: &str, start: i64, length: i64) -> Result<Vec<Vec<String>>>;
+
+    // The directory relative paths are resolved against, as an absolute path
+    // or a fully qualified URI.
+    fn working_directory(&self) -> Result<String>;
+
+    // Changes the working directory; `path` need not exist.
+    fn set_working_directory(&self, path: &str) -> Result<()>;
+
+    // Raw size of the whole filesystem in bytes.
+    fn capacity(&self) -> Result<i64>;
+
+    // Raw bytes in use, counting every replica.
+    fn used(&self) -> Result<i64>;
+
+    // The block size files created under `path` (or anywhere, for `None`) get
+    // by default.
+    fn default_block_size(&self, path: Option<&str>) -> Result<i64>;
+
+    // Hedged read counters of the whole process; few backends hedge reads.
+    fn hedged_read_metrics(&self) -> Result<HedgedReadMetrics> {
+        Err(HdfsError::new(HdfsErrorKind::Unsupported, "Hedged read metrics are not supported"))
+    }
+
+    // The native handle, for backends that have one.
+    fn raw_fs(&self) -> Option<crate::hdfsFS> {
+        None
+    }
+}
+
+// An open file of a `FileSystemBackend`.
+pub trait BackendFile: Send {
+
+    // Reads from the current stream position.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    // Reads at `position` without moving the stream position.
+    fn pread(&mut self, position: i64, buf: &mut [u8]) -> Result<usize>;
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    // Moves the stream position; only supported for files open for reading.
+    fn seek(&mut self, position: i64) -> Result<()>;
+
+    // The stream position, or the number of bytes written so far.
+    fn tell(&mut self) -> Result<i64>;
+
+    // Flushes client-side buffers.
+    fn flush(&mut self) -> Result<()>;
+
+    // Makes everything written so far visible to new readers.
+    fn hflush(&mut self) -> Result<()>;
+
+    // Like `hflush`, and also waits for the datanodes to persist the data.
+    fn hsync(&mut self) -> Result<()>;
+
+    fn close(&mut self) -> Result<()>;
+
+    // Counters of the bytes read so far; only for files open for reading.
+    fn read_statistics(&self) -> Result<ReadStatistics> {
+        Err(HdfsError::new(HdfsErrorKind::Unsupported, "Read statistics are not supported"))
+    }
+
+    fn clear_read_statistics(&mut self) -> Result<()> {
+        Err(HdfsError::new(HdfsErrorKind::Unsupported, "Read statistics are not supported"))
+    }
+
+    // The native handle, for backends that have one.
+    fn raw_file(&self) -> Option<crate::hdfsFile> {
+        None
+    }
+}
+
+
+
+// Turns `path` into `/`-separated absolute form without empty, `.` or `..`
+// components.
+pub(crate) fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => { components.pop(); },
+            _ => components.push(component),
+        }
+    }
+    ["/", components.join("/").as_str()].concat()
+}
+
+// Like `normalize`, taking relative paths from `working_dir`.
+pub(crate) fn resolve(working_dir: &str, path: &str) -> String {
+    match path.starts_with('/') {
+        true => normalize(path),
+        false => normalize(&[working_dir, "/", path].concat()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize("/"), "/");
+        assert_eq!(normalize(""), "/");
+        assert_eq!(normalize("a/b"), "/a/b");
+        assert_eq!(normalize("//a/./b/../c/"), "/a/c");
+        assert_eq!(resolve("/user/etl", "../hive/./t"), "/user/hive/t");
+        assert_eq!(resolve("/user/etl", "/tmp"), "/tmp");
+    }
+}
@@ -0,0 +1,595 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use libc::c_void;
+
+use crate::*;
+use crate::backend::{BackendFile, FileInfo, FileSystemBackend, ObjectKind, OpenRequest};
+use crate::statistics::{HedgedReadMetrics, ReadStatistics};
+use crate::error::Result;
+use crate::hdfs_fs::to_cstring;
+
+
+// A backend over a connected libhdfs `hdfsFS`, disconnected once the backend
+// and every file opened from it are dropped.
+pub struct LibHdfsBackend {
+    fs: hdfsFS,
+    handle: Arc<RawFs>,
+}
+
+struct RawFs(hdfsFS);
+
+// libhdfs filesystem handles are backed by a thread-safe Java `FileSystem`
+// and may be shared between threads.
+unsafe impl Send for RawFs {}
+unsafe impl Sync for RawFs {}
+unsafe impl Send for LibHdfsBackend {}
+unsafe impl Sync for LibHdfsBackend {}
+
+impl Drop for RawFs {
+    fn drop(&mut self) {
+        unsafe { hdfsDisconnect(self.0); }
+    }
+}
+
+impl LibHdfsBackend {
+
+    // Takes ownership of `fs`, which must be a live handle from a libhdfs
+    // connect function that nobody else disconnects.
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn from_raw(fs: hdfsFS) -> LibHdfsBackend {
+        LibHdfsBackend { fs, handle: Arc::new(RawFs(fs)) }
+    }
+}
+
+impl FileSystemBackend for LibHdfsBackend {
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        let file_path = to_cstring(path)?;
+
+        let result = unsafe {
+            hdfsExists(self.fs, file_path.as_ptr())
+        };
+
+        match result {
+            0 => Ok(true),
+            _ => {
+                let err = HdfsError::last_native(format!("Failed to check {:?}", path));
+                match err.kind() {
+                    HdfsErrorKind::NotFound => Ok(false),
+                    _ => Err(err),
+                }
+            }
+        }
+    }
+
+    fn path_info(&self, path: &str) -> Result<FileInfo> {
+        let file_path = to_cstring(path)?;
+
+        let file_info_ptr = unsafe {
+            hdfsGetPathInfo(self.fs, file_path.as_ptr())
+        };
+
+        match file_info_ptr.is_null() {
+            true => Err(HdfsError::last_native(format!("Failed to obtain info for {:?}", path))),
+            false => {
+                let file_info = unsafe { copy_file_info(&*file_info_ptr) };
+                unsafe { hdfsFreeFileInfo(file_info_ptr, 1) };
+                Ok(file_info)
+            }
+        }
+    }
+
+    // Goes through the stream builder rather than `hdfsOpenFile`, which
+    // cannot take block sizes of 2 GiB and above.
+    fn open(&self, path: &str, request: &OpenRequest) -> Result<Box<dyn BackendFile>> {
+        let file_path = to_cstring(path)?;
+        let error_message = || format!("Failed to open {:?}", path);
+
+        let opened_file = unsafe {
+            let builder = hdfsStreamBuilderAlloc(self.fs, file_path.as_ptr(), request.flags);
+            if builder.is_null() {
+                return Err(HdfsError::last_native(error_message()));
+            }
+
+            // the setters only fail on bad values; the builder is ours to
+            // free until `hdfsStreamBuilderBuild` takes it
+            let mut result = 0;
+            if request.buffer_size != 0 {
+                result = hdfsStreamBuilderSetBufferSize(builder, request.buffer_size);
+            }
+            if result == 0 && request.replication != 0 {
+                result = hdfsStreamBuilderSetReplication(builder, request.replication);
+            }
+            if result == 0 && request.block_size != 0 {
+                result = hdfsStreamBuilderSetDefaultBlockSize(builder, request.block_size);
+            }
+            if result != 0 {
+                let err = HdfsError::last_native(error_message());
+                hdfsStreamBuilderFree(builder);
+                return Err(err);
+            }
+
+            hdfsStreamBuilderBuild(builder)
+        };
+
+        match opened_file.is_null() {
+            true => Err(HdfsError::last_native(error_message())),
+            false => Ok(Box::new(LibHdfsFile {
+                fs: self.fs,
+                _handle: self.handle.clone(),
+                file: opened_file,
+                path: path.to_string(),
+            })),
+        }
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileInfo>> {
+        let file_path = to_cstring(path)?;
+
+        let mut num_entries: i32 = 0;
+        let list_result = unsafe {
+            clear_errno();
+            hdfsListDirectory(self.fs, file_path.as_ptr(), &mut num_entries)
+        };
+
+        // NULL is returned both for errors and for empty directories; only
+        // the former set errno, so it is cleared before the call.
+        if list_result.is_null() {
+            let err = HdfsError::last_native(format!("Failed to list {:?}", path));
+            return match err.errno() {
+                None => Ok(Vec::new()),
+                Some(_) => Err(err),
+            };
+        }
+
+        let entries = unsafe {
+            std::slice::from_raw_parts(list_result, num_entries as usize)
+        };
+        let file_list = entries.iter().map(|entry| unsafe { copy_file_info(entry) }).collect();
+
+        unsafe { hdfsFreeFileInfo(list_result, num_entries) };
+        Ok(file_list)
+    }
+
+    fn delete(&self, path: &str, recursive: bool) -> Result<()> {
+        let file_path = to_cstring(path)?;
+
+        let result = unsafe {
+            hdfsDelete(self.fs, file_path.as_ptr(), recursive as i32)
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to delete {:?}", path))),
+        }
+    }
+
+    fn create_dir(&self, path: &str) -> Result<()> {
+        let dir_path = to_cstring(path)?;
+
+        let result = unsafe {
+            hdfsCreateDirectory(self.fs, dir_path.as_ptr())
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to create directory {:?}", path))),
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let (from_path, to_path) = (to_cstring(from)?, to_cstring(to)?);
+
+        let result = unsafe {
+            hdfsRename(self.fs, from_path.as_ptr(), to_path.as_ptr())
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to rename {:?} to {:?}", from, to))),
+        }
+    }
+
+    fn set_permissions(&self, path: &str, mode: i16) -> Result<()> {
+        let file_path = to_cstring(path)?;
+
+        let result = unsafe {
+            hdfsChmod(self.fs, file_path.as_ptr(), mode)
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to set permissions of {:?}", path))),
+        }
+    }
+
+    fn set_owner(&self, path: &str, owner: Option<&str>, group: Option<&str>) -> Result<()> {
+        let file_path = to_cstring(path)?;
+        let owner = owner.map(to_cstring).transpose()?;
+        let group = group.map(to_cstring).transpose()?;
+
+        let result = unsafe {
+            hdfsChown(self.fs, file_path.as_ptr(),
+                owner.as_ref().map_or(std::ptr::null(), |owner| owner.as_ptr()),
+                group.as_ref().map_or(std::ptr::null(), |group| group.as_ptr()))
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to set owner of {:?}", path))),
+        }
+    }
+
+    fn set_times(&self, path: &str, last_mod: Option<i64>, last_access: Option<i64>) -> Result<()> {
+        let file_path = to_cstring(path)?;
+
+        let result = unsafe {
+            hdfsUtime(self.fs, file_path.as_ptr(), last_mod.unwrap_or(-1), last_access.unwrap_or(-1))
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to set times of {:?}", path))),
+        }
+    }
+
+    fn set_replication(&self, path: &str, replication: i16) -> Result<()> {
+        let file_path = to_cstring(path)?;
+
+        let result = unsafe {
+            hdfsSetReplication(self.fs, file_path.as_ptr(), replication)
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to set replication of {:?}", path))),
+        }
+    }
+
+    fn hosts(&self, path: &str, start: i64, length: i64) -> Result<Vec<Vec<String>>> {
+        let file_path = to_cstring(path)?;
+
+        unsafe {
+            let block_hosts = hdfsGetHosts(self.fs, file_path.as_ptr(), start, length);
+            if block_hosts.is_null() {
+                return Err(HdfsError::last_native(format!("Failed to get hosts for {:?}", path)));
+            }
+
+            // both levels are NULL terminated arrays
+            let mut blocks = Vec::new();
+            let mut block = block_hosts;
+            while !(*block).is_null() {
+                let mut hosts = Vec::new();
+                let mut host = *block;
+                while !(*host).is_null() {
+                    hosts.push(CStr::from_ptr(*host).to_string_lossy().into_owned());
+                    host = host.add(1);
+                }
+                blocks.push(hosts);
+                block = block.add(1);
+            }
+
+            hdfsFreeHosts(block_hosts);
+            Ok(blocks)
+        }
+    }
+
+    fn working_directory(&self) -> Result<String> {
+        let mut buf = vec![0u8; 4096];
+        let result = unsafe {
+            hdfsGetWorkingDirectory(self.fs, buf.as_mut_ptr() as *mut std::os::raw::c_char, buf.len() as u64)
+        };
+
+        if result.is_null() {
+            return Err(HdfsError::last_native("Failed to obtain the working directory"));
+        }
+
+        // older versions `strncpy` into the buffer, leaving a path that
+        // fills it unterminated
+        match CStr::from_bytes_until_nul(&buf) {
+            Ok(working_dir) => Ok(working_dir.to_string_lossy().into_owned()),
+            Err(_) => Err(HdfsError::new(HdfsErrorKind::Other,
+                format!("Working directory longer than {} bytes", buf.len()))),
+        }
+    }
+
+    fn set_working_directory(&self, path: &str) -> Result<()> {
+        let file_path = to_cstring(path)?;
+
+        let result = unsafe {
+            hdfsSetWorkingDirectory(self.fs, file_path.as_ptr())
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to change the working directory to {:?}", path))),
+        }
+    }
+
+    fn capacity(&self) -> Result<i64> {
+        let capacity = unsafe {
+            hdfsGetCapacity(self.fs)
+        };
+
+        match capacity {
+            -1 => Err(HdfsError::last_native("Failed to obtain the capacity")),
+            capacity => Ok(capacity),
+        }
+    }
+
+    fn used(&self) -> Result<i64> {
+        let used = unsafe {
+            hdfsGetUsed(self.fs)
+        };
+
+        match used {
+            -1 => Err(HdfsError::last_native("Failed to obtain the used space")),
+            used => Ok(used),
+        }
+    }
+
+    fn default_block_size(&self, path: Option<&str>) -> Result<i64> {
+        let block_size = match path {
+            Some(path) => {
+                let file_path = to_cstring(path)?;
+                unsafe { hdfsGetDefaultBlockSizeAtPath(self.fs, file_path.as_ptr()) }
+            }
+            None => unsafe { hdfsGetDefaultBlockSize(self.fs) },
+        };
+
+        match block_size {
+            -1 => Err(HdfsError::last_native(format!("Failed to obtain the default block size at {:?}", path.unwrap_or("/")))),
+            block_size => Ok(block_size),
+        }
+    }
+
+    fn hedged_read_metrics(&self) -> Result<HedgedReadMetrics> {
+        let mut metrics_ptr = std::ptr::null_mut();
+        let result = unsafe {
+            hdfsGetHedgedReadMetrics(self.fs, &mut metrics_ptr)
+        };
+
+        if result != 0 {
+            return Err(HdfsError::last_native("Failed to obtain hedged read metrics"));
+        }
+
+        let metrics = unsafe {
+            let metrics = &*metrics_ptr;
+            HedgedReadMetrics {
+                ops: metrics.hedgedReadOps,
+                ops_won: metrics.hedgedReadOpsWin,
+                ops_in_current_thread: metrics.hedgedReadOpsInCurThread,
+            }
+        };
+        unsafe { hdfsFreeHedgedReadMetrics(metrics_ptr) };
+        Ok(metrics)
+    }
+
+    fn raw_fs(&self) -> Option<hdfsFS> {
+        Some(self.fs)
+    }
+}
+
+
+// Copies a file or tree between two connected filesystems (`hdfsCopy`), or
+// moves it (`hdfsMove`). Both go through Hadoop's `FileUtil.copy`.
+pub(crate) fn copy_between(src_fs: hdfsFS, src: &str, dst_fs: hdfsFS, dst: &str, move_source: bool) -> Result<()> {
+    let (src_path, dst_path) = (to_cstring(src)?, to_cstring(dst)?);
+
+    let result = unsafe {
+        match move_source {
+            true => hdfsMove(src_fs, src_path.as_ptr(), dst_fs, dst_path.as_ptr()),
+            false => hdfsCopy(src_fs, src_path.as_ptr(), dst_fs, dst_path.as_ptr()),
+        }
+    };
+
+    match result {
+        0 => Ok(()),
+        _ => Err(HdfsError::last_native(format!("Failed to copy {:?} to {:?}", src, dst))),
+    }
+}
+
+
+struct LibHdfsFile {
+    fs: hdfsFS,
+    // keeps `fs` connected while the file is open
+    _handle: Arc<RawFs>,
+    file: hdfsFile,
+    path: String,
+}
+
+// A file handle is only ever used through `&mut self`, and libhdfs attaches
+// whichever thread calls into it to the JVM.
+unsafe impl Send for LibHdfsFile {}
+
+impl BackendFile for LibHdfsFile {
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let length = std::cmp::min(buf.len(), i32::MAX as usize) as tSize;
+        let read_bytes = unsafe {
+            hdfsRead(self.fs, self.file, buf.as_mut_ptr() as *mut c_void, length)
+        };
+
+        match read_bytes {
+            -1 => Err(HdfsError::last_native(format!("Failed to read {:?}", self.path))),
+            _ => Ok(read_bytes as usize),
+        }
+    }
+
+    fn pread(&mut self, position: i64, buf: &mut [u8]) -> Result<usize> {
+        let length = std::cmp::min(buf.len(), i32::MAX as usize) as tSize;
+        let read_bytes = unsafe {
+            hdfsPread(self.fs, self.file, position, buf.as_mut_ptr() as *mut c_void, length)
+        };
+
+        match read_bytes {
+            -1 => Err(HdfsError::last_native(format!("Failed to read {:?}", self.path))),
+            _ => Ok(read_bytes as usize),
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let length = std::cmp::min(buf.len(), i32::MAX as usize) as tSize;
+        let written_bytes = unsafe {
+            hdfsWrite(self.fs, self.file, buf.as_ptr() as *const c_void, length)
+        };
+
+        match written_bytes {
+            -1 => Err(HdfsError::last_native(format!("Failed to write {:?}", self.path))),
+            _ => Ok(written_bytes as usize),
+        }
+    }
+
+    fn seek(&mut self, position: i64) -> Result<()> {
+        let result = unsafe {
+            hdfsSeek(self.fs, self.file, position)
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to seek {:?} to {}", self.path, position))),
+        }
+    }
+
+    fn tell(&mut self) -> Result<i64> {
+        let position = unsafe {
+            hdfsTell(self.fs, self.file)
+        };
+
+        match position {
+            -1 => Err(HdfsError::last_native(format!("Failed to get position in {:?}", self.path))),
+            _ => Ok(position),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let result = unsafe {
+            hdfsFlush(self.fs, self.file)
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to flush {:?}", self.path))),
+        }
+    }
+
+    fn hflush(&mut self) -> Result<()> {
+        let result = unsafe {
+            hdfsHFlush(self.fs, self.file)
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to hflush {:?}", self.path))),
+        }
+    }
+
+    fn hsync(&mut self) -> Result<()> {
+        let result = unsafe {
+            hdfsHSync(self.fs, self.file)
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to hsync {:?}", self.path))),
+        }
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if self.file.is_null() {
+            return Ok(());
+        }
+
+        let result = unsafe {
+            hdfsCloseFile(self.fs, self.file)
+        };
+        // the handle is freed even if closing failed
+        self.file = std::ptr::null_mut();
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to close {:?}", self.path))),
+        }
+    }
+
+    fn read_statistics(&self) -> Result<ReadStatistics> {
+        let mut stats_ptr = std::ptr::null_mut();
+        let result = unsafe {
+            hdfsFileGetReadStatistics(self.file, &mut stats_ptr)
+        };
+
+        if result != 0 {
+            return Err(HdfsError::last_native(format!("Failed to obtain read statistics of {:?}", self.path)));
+        }
+
+        let statistics = unsafe {
+            let stats = &*stats_ptr;
+            ReadStatistics {
+                total_bytes: stats.totalBytesRead,
+                local_bytes: stats.totalLocalBytesRead,
+                short_circuit_bytes: stats.totalShortCircuitBytesRead,
+                zero_copy_bytes: stats.totalZeroCopyBytesRead,
+                remote_bytes: std::cmp::max(hdfsReadStatisticsGetRemoteBytesRead(stats), 0) as u64,
+            }
+        };
+        unsafe { hdfsFileFreeReadStatistics(stats_ptr) };
+        Ok(statistics)
+    }
+
+    fn clear_read_statistics(&mut self) -> Result<()> {
+        let result = unsafe {
+            hdfsFileClearReadStatistics(self.file)
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(HdfsError::last_native(format!("Failed to clear read statistics of {:?}", self.path))),
+        }
+    }
+
+    fn raw_file(&self) -> Option<hdfsFile> {
+        Some(self.file)
+    }
+}
+
+impl Drop for LibHdfsFile {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+
+unsafe fn copy_file_info(info: &hdfsFileInfo) -> FileInfo {
+    FileInfo {
+        kind: match info.mKind {
+            tObjectKind_kObjectKindDirectory => ObjectKind::Directory,
+            _ => ObjectKind::File,
+        },
+        name: copy_c_str(info.mName),
+        last_mod: info.mLastMod,
+        size: info.mSize,
+        replication: info.mReplication,
+        block_size: info.mBlockSize,
+        owner: copy_c_str(info.mOwner),
+        group: copy_c_str(info.mGroup),
+        permissions: info.mPermissions,
+        last_access: info.mLastAccess,
+    }
+}
+
+unsafe fn copy_c_str(ptr: *const std::os::raw::c_char) -> String {
+    match ptr.is_null() {
+        true => String::new(),
+        false => CStr::from_ptr(ptr).to_string_lossy().into_owned(),
+    }
+}
+
+// Calls that report errors only through errno need it cleared first, or a
+// value left over from earlier work on the thread looks like their error.
+unsafe fn clear_errno() {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    { *libc::__errno_location() = 0; }
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    { *libc::__error() = 0; }
+}
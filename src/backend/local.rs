@@ -0,0 +1,567 @@
+use std::ffi::{CStr, CString};
+use std::fs::{File, FileTimes, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::{HdfsError, HdfsErrorKind, O_ACCMODE, O_APPEND, O_RDONLY};
+use crate::backend::{resolve, BackendFile, FileInfo, FileSystemBackend, ObjectKind, OpenRequest};
+use crate::error::Result;
+use crate::hdfs_fs::to_cstring;
+
+
+const DEFAULT_BLOCK_SIZE: i64 = 128 * 1024 * 1024;
+const DEFAULT_REPLICATION: i16 = 1;
+
+
+// Serves HDFS paths from a directory on the local disk, so pipelines can run
+// without a cluster. `/a/b` maps to `<root>/a/b`, relative paths are taken
+// from the working directory (`/` at first), and `..` never escapes the
+// root. Owner, group and permissions come from the local files, while block
+// size and replication are emulated.
+#[derive(Clone, Debug)]
+pub struct LocalBackend {
+    root: PathBuf,
+    working_dir: Arc<Mutex<String>>,
+    block_size: i64,
+    replication: i16,
+}
+
+impl LocalBackend {
+
+    pub fn new<P: Into<PathBuf>>(root: P) -> Result<LocalBackend> {
+        let root = root.into();
+        if !std::fs::metadata(&root)?.is_dir() {
+            return Err(HdfsError::new(HdfsErrorKind::NotADirectory,
+                format!("Local root {:?} is not a directory", root)));
+        }
+
+        Ok(LocalBackend {
+            root,
+            working_dir: Arc::new(Mutex::new(String::from("/"))),
+            block_size: DEFAULT_BLOCK_SIZE,
+            replication: DEFAULT_REPLICATION,
+        })
+    }
+
+    // Block size reported for files and used to split them into blocks;
+    // non-positive sizes fall back to the default.
+    pub fn with_block_size(mut self, block_size: i64) -> LocalBackend {
+        self.block_size = match block_size {
+            block_size if block_size > 0 => block_size,
+            _ => DEFAULT_BLOCK_SIZE,
+        };
+        self
+    }
+
+    // Replication reported for files.
+    pub fn with_replication(mut self, replication: i16) -> LocalBackend {
+        self.replication = replication;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn local_path(&self, path: &str) -> (String, PathBuf) {
+        let path = resolve(&self.working_dir.lock().unwrap_or_else(|err| err.into_inner()), path);
+        let local_path = self.root.join(&path[1..]);
+        (path, local_path)
+    }
+
+    fn info(&self, path: String, metadata: &std::fs::Metadata) -> FileInfo {
+        let is_dir = metadata.is_dir();
+        FileInfo {
+            kind: if is_dir { ObjectKind::Directory } else { ObjectKind::File },
+            name: path,
+            last_mod: metadata.mtime(),
+            size: if is_dir { 0 } else { metadata.len() as i64 },
+            replication: if is_dir { 0 } else { self.replication },
+            block_size: if is_dir { 0 } else { self.block_size },
+            owner: user_name(metadata.uid()),
+            group: group_name(metadata.gid()),
+            permissions: (metadata.mode() & 0o7777) as i16,
+            last_access: metadata.atime(),
+        }
+    }
+
+    // Usage of the local filesystem holding the root.
+    fn statvfs(&self) -> Result<libc::statvfs> {
+        let root = CString::new(self.root.as_os_str().as_bytes()).map_err(|_| HdfsError::new(
+            HdfsErrorKind::InvalidInput, format!("Invalid local root {:?}", self.root)))?;
+
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        match unsafe { libc::statvfs(root.as_ptr(), &mut stat) } {
+            0 => Ok(stat),
+            _ => Err(std::io::Error::last_os_error().into()),
+        }
+    }
+}
+
+impl FileSystemBackend for LocalBackend {
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        let (_, local_path) = self.local_path(path);
+        match std::fs::metadata(local_path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn path_info(&self, path: &str) -> Result<FileInfo> {
+        let (path, local_path) = self.local_path(path);
+        let metadata = std::fs::metadata(local_path)?;
+        Ok(self.info(path, &metadata))
+    }
+
+    fn open(&self, path: &str, request: &OpenRequest) -> Result<Box<dyn BackendFile>> {
+        let (path, local_path) = self.local_path(path);
+
+        if std::fs::metadata(&local_path).map(|metadata| metadata.is_dir()).unwrap_or(false) {
+            return Err(HdfsError::new(HdfsErrorKind::Other,
+                format!("{:?} is a directory", path)));
+        }
+
+        let file = if request.flags as u32 & O_ACCMODE == O_RDONLY {
+            File::open(&local_path)?
+        } else if request.flags as u32 & O_APPEND != 0 {
+            OpenOptions::new().append(true).open(&local_path)?
+        } else {
+            // like HDFS, creating a file creates its missing parents; the
+            // requested replication and block size have no local equivalent
+            // and the backend-wide values keep being reported
+            if let Some(parent) = local_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            File::create(&local_path)?
+        };
+
+        Ok(Box::new(LocalFile { file: Some(file), path }))
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileInfo>> {
+        let (path, local_path) = self.local_path(path);
+
+        let metadata = std::fs::metadata(&local_path)?;
+        if !metadata.is_dir() {
+            return Ok(vec![self.info(path, &metadata)]);
+        }
+
+        let mut file_list = Vec::new();
+        for entry in std::fs::read_dir(&local_path)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().map_err(|name| HdfsError::new(
+                HdfsErrorKind::InvalidInput, format!("Non UTF-8 file name {:?} in {:?}", name, path)))?;
+            let child = match path.as_str() {
+                "/" => ["/", name.as_str()].concat(),
+                _ => [path.as_str(), "/", name.as_str()].concat(),
+            };
+            file_list.push(self.info(child, &entry.metadata()?));
+        }
+
+        // HDFS lists directories in name order
+        file_list.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(file_list)
+    }
+
+    fn delete(&self, path: &str, recursive: bool) -> Result<()> {
+        let (path, local_path) = self.local_path(path);
+
+        if path == "/" {
+            return Err(HdfsError::new(HdfsErrorKind::PermissionDenied, "Cannot delete the root directory"));
+        }
+
+        match (std::fs::symlink_metadata(&local_path)?.is_dir(), recursive) {
+            (false, _) => std::fs::remove_file(&local_path)?,
+            (true, false) => std::fs::remove_dir(&local_path)?,
+            (true, true) => std::fs::remove_dir_all(&local_path)?,
+        }
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &str) -> Result<()> {
+        let (_, local_path) = self.local_path(path);
+        Ok(std::fs::create_dir_all(local_path)?)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let (from, local_from) = self.local_path(from);
+        let (to, local_to) = self.local_path(to);
+
+        if from == "/" || to.starts_with(&[from.as_str(), "/"].concat()) {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Cannot move {:?} into {:?}", from, to)));
+        }
+        // `std::fs::rename` would silently replace an existing file
+        if self.exists(&to)? {
+            return Err(HdfsError::new(HdfsErrorKind::AlreadyExists,
+                format!("{:?} already exists", to)));
+        }
+
+        Ok(std::fs::rename(local_from, local_to)?)
+    }
+
+    fn set_permissions(&self, path: &str, mode: i16) -> Result<()> {
+        let (_, local_path) = self.local_path(path);
+        let permissions = std::fs::Permissions::from_mode(mode as u16 as u32 & 0o1777);
+        Ok(std::fs::set_permissions(local_path, permissions)?)
+    }
+
+    fn set_owner(&self, path: &str, owner: Option<&str>, group: Option<&str>) -> Result<()> {
+        let (_, local_path) = self.local_path(path);
+        let uid = owner.map(user_id).transpose()?;
+        let gid = group.map(group_id).transpose()?;
+        Ok(std::os::unix::fs::chown(local_path, uid, gid)?)
+    }
+
+    fn set_times(&self, path: &str, last_mod: Option<i64>, last_access: Option<i64>) -> Result<()> {
+        let (_, local_path) = self.local_path(path);
+        let to_time = |seconds: i64| UNIX_EPOCH + Duration::from_secs(std::cmp::max(seconds, 0) as u64);
+
+        let mut times = FileTimes::new();
+        if let Some(last_mod) = last_mod {
+            times = times.set_modified(to_time(last_mod));
+        }
+        if let Some(last_access) = last_access {
+            times = times.set_accessed(to_time(last_access));
+        }
+        Ok(File::open(local_path)?.set_times(times)?)
+    }
+
+    // replication is emulated for the whole backend, so there is nothing to
+    // change; directories are refused as on HDFS
+    fn set_replication(&self, path: &str, _replication: i16) -> Result<()> {
+        match self.path_info(path)?.kind {
+            ObjectKind::File => Ok(()),
+            ObjectKind::Directory => Err(HdfsError::new(HdfsErrorKind::IsADirectory,
+                format!("Directories have no replication: {:?}", path))),
+        }
+    }
+
+    fn hosts(&self, path: &str, start: i64, length: i64) -> Result<Vec<Vec<String>>> {
+        let (_, local_path) = self.local_path(path);
+        let metadata = std::fs::metadata(local_path)?;
+
+        let end = std::cmp::min(start.saturating_add(length), metadata.len() as i64);
+        if metadata.is_dir() || start < 0 || start >= end {
+            return Ok(Vec::new());
+        }
+
+        let blocks = (end - 1) / self.block_size - start / self.block_size + 1;
+        Ok((0..blocks).map(|_| vec![String::from("localhost")]).collect())
+    }
+
+    fn working_directory(&self) -> Result<String> {
+        Ok(self.working_dir.lock().unwrap_or_else(|err| err.into_inner()).clone())
+    }
+
+    fn set_working_directory(&self, path: &str) -> Result<()> {
+        let (path, _) = self.local_path(path);
+        *self.working_dir.lock().unwrap_or_else(|err| err.into_inner()) = path;
+        Ok(())
+    }
+
+    fn capacity(&self) -> Result<i64> {
+        let stat = self.statvfs()?;
+        Ok((stat.f_blocks as i64).saturating_mul(stat.f_frsize as i64))
+    }
+
+    // only counts what the whole local filesystem holds, without replicas
+    fn used(&self) -> Result<i64> {
+        let stat = self.statvfs()?;
+        Ok((stat.f_blocks.saturating_sub(stat.f_bfree) as i64).saturating_mul(stat.f_frsize as i64))
+    }
+
+    fn default_block_size(&self, _path: Option<&str>) -> Result<i64> {
+        Ok(self.block_size)
+    }
+}
+
+
+struct LocalFile {
+    file: Option<File>,
+    path: String,
+}
+
+impl LocalFile {
+
+    fn file(&mut self) -> Result<&mut File> {
+        let path = &self.path;
+        self.file.as_mut().ok_or_else(|| HdfsError::new(HdfsErrorKind::NotConnected,
+            format!("File is not open: {:?}", path)))
+    }
+}
+
+impl BackendFile for LocalFile {
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.file()?.read(buf)?)
+    }
+
+    fn pread(&mut self, position: i64, buf: &mut [u8]) -> Result<usize> {
+        if position < 0 {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Negative read position {} in {:?}", position, self.path)));
+        }
+        Ok(self.file()?.read_at(buf, position as u64)?)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Ok(self.file()?.write(buf)?)
+    }
+
+    fn seek(&mut self, position: i64) -> Result<()> {
+        if position < 0 {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Cannot seek {:?} to {}", self.path, position)));
+        }
+        self.file()?.seek(SeekFrom::Start(position as u64))?;
+        Ok(())
+    }
+
+    fn tell(&mut self) -> Result<i64> {
+        Ok(self.file()?.stream_position()? as i64)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.file()?.flush()?)
+    }
+
+    fn hflush(&mut self) -> Result<()> {
+        Ok(self.file()?.flush()?)
+    }
+
+    fn hsync(&mut self) -> Result<()> {
+        Ok(self.file()?.sync_data()?)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.file = None;
+        Ok(())
+    }
+}
+
+
+fn user_name(uid: u32) -> String {
+    let mut buf = vec![0; 4096];
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+
+    let rc = unsafe {
+        libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result)
+    };
+
+    match rc == 0 && !result.is_null() {
+        true => unsafe { CStr::from_ptr(passwd.pw_name).to_string_lossy().into_owned() },
+        false => uid.to_string(),
+    }
+}
+
+fn group_name(gid: u32) -> String {
+    let mut buf = vec![0; 4096];
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+
+    let rc = unsafe {
+        libc::getgrgid_r(gid, &mut group, buf.as_mut_ptr(), buf.len(), &mut result)
+    };
+
+    match rc == 0 && !result.is_null() {
+        true => unsafe { CStr::from_ptr(group.gr_name).to_string_lossy().into_owned() },
+        false => gid.to_string(),
+    }
+}
+
+// Resolves a user name, or a numeric uid as reported for unknown users.
+fn user_id(name: &str) -> Result<u32> {
+    let c_name = to_cstring(name)?;
+    let mut buf = vec![0; 4096];
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+
+    let rc = unsafe {
+        libc::getpwnam_r(c_name.as_ptr(), &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result)
+    };
+
+    match rc == 0 && !result.is_null() {
+        true => Ok(passwd.pw_uid),
+        false => name.parse().map_err(|_| HdfsError::new(HdfsErrorKind::InvalidInput,
+            format!("Unknown user {:?}", name))),
+    }
+}
+
+fn group_id(name: &str) -> Result<u32> {
+    let c_name = to_cstring(name)?;
+    let mut buf = vec![0; 4096];
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+
+    let rc = unsafe {
+        libc::getgrnam_r(c_name.as_ptr(), &mut group, buf.as_mut_ptr(), buf.len(), &mut result)
+    };
+
+    match rc == 0 && !result.is_null() {
+        true => Ok(group.gr_gid),
+        false => name.parse().map_err(|_| HdfsError::new(HdfsErrorKind::InvalidInput,
+            format!("Unknown group {:?}", name))),
+    }
+}
+
+// A fresh directory under the system temp dir, removed on drop.
+#[cfg(test)]
+pub(crate) struct TempRoot(pub(crate) PathBuf);
+
+#[cfg(test)]
+impl TempRoot {
+    pub(crate) fn new() -> TempRoot {
+        use rand::{thread_rng, Rng};
+        use rand::distributions::Alphanumeric;
+
+        let name: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+        let root = std::env::temp_dir().join(["rust-hdfs-", name.as_str()].concat());
+        std::fs::create_dir(&root).unwrap();
+        TempRoot(root)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempRoot {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{O_CREAT, O_WRONLY};
+
+    fn write_file(backend: &LocalBackend, path: &str, content: &[u8]) {
+        let mut file = backend.open(path, &OpenRequest::new((O_WRONLY | O_CREAT) as i32)).unwrap();
+        file.write(content).unwrap();
+        file.close().unwrap();
+    }
+
+    #[test]
+    fn maps_paths_under_root() {
+        let root = TempRoot::new();
+        let backend = LocalBackend::new(&root.0).unwrap();
+        write_file(&backend, "/warehouse/../../t/part-0", b"abc");
+
+        assert!(root.0.join("t/part-0").is_file());
+        assert_eq!(std::fs::read(root.0.join("t/part-0")).unwrap(), b"abc");
+        assert_eq!(backend.list("/").unwrap()[0].name, "/t");
+    }
+
+    #[test]
+    fn non_positive_block_sizes_use_the_default() {
+        let root = TempRoot::new();
+        for block_size in &[0, -1] {
+            let backend = LocalBackend::new(&root.0).unwrap().with_block_size(*block_size);
+            write_file(&backend, "/f", b"0123456789");
+            assert_eq!(backend.path_info("/f").unwrap().block_size, DEFAULT_BLOCK_SIZE);
+            assert_eq!(backend.hosts("/f", 0, 10).unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn emulates_file_info() {
+        let root = TempRoot::new();
+        let backend = LocalBackend::new(&root.0).unwrap().with_block_size(4).with_replication(2);
+        write_file(&backend, "/d/f", b"0123456789");
+        std::fs::set_permissions(root.0.join("d/f"),
+            std::os::unix::fs::PermissionsExt::from_mode(0o640)).unwrap();
+
+        let info = backend.path_info("/d/f").unwrap();
+        assert_eq!(info.kind, ObjectKind::File);
+        assert_eq!(info.name, "/d/f");
+        assert_eq!(info.size, 10);
+        assert_eq!(info.block_size, 4);
+        assert_eq!(info.replication, 2);
+        assert_eq!(info.permissions, 0o640);
+        assert!(!info.owner.is_empty());
+        assert!(!info.group.is_empty());
+        assert_eq!(backend.hosts("/d/f", 0, 10).unwrap().len(), 3);
+
+        let dir = backend.path_info("/d").unwrap();
+        assert_eq!(dir.kind, ObjectKind::Directory);
+        assert_eq!((dir.size, dir.block_size, dir.replication), (0, 0, 0));
+    }
+
+    #[test]
+    fn read_append_and_delete() {
+        let root = TempRoot::new();
+        let backend = LocalBackend::new(&root.0).unwrap();
+        write_file(&backend, "/f", b"hello");
+
+        let mut appender = backend.open("/f", &OpenRequest::new((O_WRONLY | O_APPEND) as i32)).unwrap();
+        appender.write(b" world").unwrap();
+        appender.close().unwrap();
+
+        let mut reader = backend.open("/f", &OpenRequest::new(O_RDONLY as i32)).unwrap();
+        let mut buf = [0u8; 5];
+        assert_eq!(reader.pread(6, &mut buf).unwrap(), 5);
+        assert_eq!(&buf, b"world");
+
+        assert!(backend.delete("/", true).is_err());
+        write_file(&backend, "/d/e", b"");
+        assert!(backend.delete("/d", false).is_err());
+        backend.delete("/d", true).unwrap();
+        assert!(!backend.exists("/d").unwrap());
+        assert_eq!(backend.open("/d", &OpenRequest::new(O_RDONLY as i32)).err().unwrap().kind(), HdfsErrorKind::NotFound);
+    }
+
+    #[test]
+    fn sets_permissions_owner_and_times() {
+        let root = TempRoot::new();
+        let backend = LocalBackend::new(&root.0).unwrap();
+        write_file(&backend, "/f", b"");
+
+        backend.set_permissions("/f", 0o600).unwrap();
+        backend.set_times("/f", Some(1_000_000_000), None).unwrap();
+        let info = backend.path_info("/f").unwrap();
+        assert_eq!(info.permissions, 0o600);
+        assert_eq!(info.last_mod, 1_000_000_000);
+
+        // handing a file to its current owner is always allowed
+        backend.set_owner("/f", Some(&info.owner), Some(&info.group)).unwrap();
+        assert_eq!(backend.set_owner("/f", Some("no such user"), None).unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn rename_never_replaces() {
+        let root = TempRoot::new();
+        let backend = LocalBackend::new(&root.0).unwrap();
+        write_file(&backend, "/a", b"a");
+        write_file(&backend, "/b", b"b");
+        backend.create_dir("/d/e").unwrap();
+
+        assert_eq!(backend.rename("/a", "/b").unwrap_err().kind(), HdfsErrorKind::AlreadyExists);
+        assert_eq!(backend.rename("/d", "/d/e/f").unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+        backend.rename("/a", "/d/e/a").unwrap();
+        assert_eq!(std::fs::read(root.0.join("d/e/a")).unwrap(), b"a");
+        assert_eq!(std::fs::read(root.0.join("b")).unwrap(), b"b");
+    }
+
+    #[test]
+    fn file_uri_selects_local_backend() {
+        let root = TempRoot::new();
+        let uri = ["file://", root.0.to_str().unwrap()].concat();
+        let fs = crate::HdfsFileSystem::connect(uri.as_str()).unwrap();
+        assert!(fs.as_raw().is_none());
+
+        fs.create("/pipeline/out").unwrap().write_all(b"rows").unwrap();
+        let mut content = String::new();
+        crate::HdfsFileSystem::connect(uri.as_str()).unwrap()
+            .open("/pipeline/out").unwrap()
+            .read_to_string(&mut content).unwrap();
+        assert_eq!(content, "rows");
+        assert_eq!(fs.read_dir("/pipeline").unwrap().next().unwrap().unwrap().path(), Path::new("/pipeline/out"));
+    }
+}
@@ -0,0 +1,660 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{HdfsError, HdfsErrorKind, ReadStatistics, O_ACCMODE, O_APPEND, O_RDONLY};
+use crate::backend::{resolve, BackendFile, FileInfo, FileSystemBackend, ObjectKind, OpenRequest};
+use crate::error::Result;
+
+
+const DEFAULT_BLOCK_SIZE: i64 = 128 * 1024 * 1024;
+const DEFAULT_REPLICATION: i16 = 3;
+const DEFAULT_CAPACITY: i64 = 1 << 40;
+
+
+// A pure-Rust, in-process backend. Paths are normalized to absolute paths
+// (relative ones are taken from the working directory, `/` at first), writes
+// are visible to readers as soon as they happen, and every block is reported
+// as served by `localhost`.
+#[derive(Clone)]
+pub struct MemoryBackend {
+    tree: Arc<Mutex<Tree>>,
+    working_dir: Arc<Mutex<String>>,
+    block_size: i64,
+    replication: i16,
+    capacity: i64,
+    max_read_size: usize,
+    max_write_size: usize,
+}
+
+struct Node {
+    kind: ObjectKind,
+    data: Vec<u8>,
+    last_mod: i64,
+    last_access: i64,
+    replication: i16,
+    block_size: i64,
+    owner: String,
+    group: String,
+    permissions: i16,
+}
+
+type Tree = BTreeMap<String, Node>;
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        MemoryBackend::new()
+    }
+}
+
+impl MemoryBackend {
+
+    pub fn new() -> MemoryBackend {
+        let mut tree = Tree::new();
+        tree.insert(String::from("/"), Node::directory());
+
+        MemoryBackend {
+            tree: Arc::new(Mutex::new(tree)),
+            working_dir: Arc::new(Mutex::new(String::from("/"))),
+            block_size: DEFAULT_BLOCK_SIZE,
+            replication: DEFAULT_REPLICATION,
+            capacity: DEFAULT_CAPACITY,
+            max_read_size: usize::MAX,
+            max_write_size: usize::MAX,
+        }
+    }
+
+    // Block size given to files created from now on; non-positive sizes
+    // fall back to the default.
+    pub fn with_block_size(mut self, block_size: i64) -> MemoryBackend {
+        self.block_size = match block_size {
+            block_size if block_size > 0 => block_size,
+            _ => DEFAULT_BLOCK_SIZE,
+        };
+        self
+    }
+
+    // Replication given to files created from now on.
+    pub fn with_replication(mut self, replication: i16) -> MemoryBackend {
+        self.replication = replication;
+        self
+    }
+
+    // Raw capacity reported for the filesystem; 1 TiB by default.
+    pub fn with_capacity(mut self, capacity: i64) -> MemoryBackend {
+        self.capacity = capacity;
+        self
+    }
+
+    // Makes every read return at most `max_read_size` bytes, like a remote
+    // read stopping at a packet or block boundary.
+    pub fn with_max_read_size(mut self, max_read_size: usize) -> MemoryBackend {
+        self.max_read_size = max_read_size;
+        self
+    }
+
+    // Makes every write accept at most `max_write_size` bytes.
+    pub fn with_max_write_size(mut self, max_write_size: usize) -> MemoryBackend {
+        self.max_write_size = max_write_size;
+        self
+    }
+
+    fn file(&self, path: String, writable: bool) -> Box<dyn BackendFile> {
+        Box::new(MemoryFile {
+            tree: self.tree.clone(),
+            path,
+            position: 0,
+            writable,
+            bytes_read: 0,
+            max_read_size: self.max_read_size,
+            max_write_size: self.max_write_size,
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tree> {
+        lock_tree(&self.tree)
+    }
+
+    fn absolute(&self, path: &str) -> String {
+        let working_dir = self.working_dir.lock().unwrap_or_else(|err| err.into_inner());
+        resolve(&working_dir, path)
+    }
+
+    fn update<F: FnOnce(&mut Node)>(&self, path: &str, update: F) -> Result<()> {
+        let path = self.absolute(path);
+        let mut tree = self.lock();
+        let node = tree.get_mut(&path).ok_or_else(|| not_found(&path))?;
+        update(node);
+        Ok(())
+    }
+}
+
+impl FileSystemBackend for MemoryBackend {
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        let path = self.absolute(path);
+        Ok(self.lock().contains_key(&path))
+    }
+
+    fn path_info(&self, path: &str) -> Result<FileInfo> {
+        let path = self.absolute(path);
+        let tree = self.lock();
+        let node = lookup(&tree, &path)?;
+        Ok(node.info(&path))
+    }
+
+    fn open(&self, path: &str, request: &OpenRequest) -> Result<Box<dyn BackendFile>> {
+        let path = self.absolute(path);
+        let mut tree = self.lock();
+
+        if request.flags as u32 & O_ACCMODE == O_RDONLY {
+            if lookup(&tree, &path)?.kind == ObjectKind::Directory {
+                return Err(HdfsError::new(HdfsErrorKind::Other,
+                    format!("Cannot open directory {:?} for reading", path)));
+            }
+            return Ok(self.file(path, false));
+        }
+
+        if request.flags as u32 & O_APPEND != 0 {
+            if lookup(&tree, &path)?.kind == ObjectKind::Directory {
+                return Err(HdfsError::new(HdfsErrorKind::Other,
+                    format!("Cannot append to directory {:?}", path)));
+            }
+            return Ok(self.file(path, true));
+        }
+
+        if let Some(node) = tree.get(&path) {
+            if node.kind == ObjectKind::Directory {
+                return Err(HdfsError::new(HdfsErrorKind::AlreadyExists,
+                    format!("{:?} already exists as a directory", path)));
+            }
+        }
+        create_parents(&mut tree, &path)?;
+
+        let mut node = Node::directory();
+        node.kind = ObjectKind::File;
+        node.replication = match request.replication {
+            0 => self.replication,
+            replication => replication,
+        };
+        node.block_size = match request.block_size {
+            block_size if block_size > 0 => block_size,
+            _ => self.block_size,
+        };
+        node.permissions = 0o644;
+        tree.insert(path.clone(), node);
+
+        Ok(self.file(path, true))
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileInfo>> {
+        let path = self.absolute(path);
+        let tree = self.lock();
+
+        let node = lookup(&tree, &path)?;
+        if node.kind == ObjectKind::File {
+            return Ok(vec![node.info(&path)]);
+        }
+
+        Ok(children(&tree, &path)
+            .map(|(child_path, child)| child.info(child_path))
+            .collect())
+    }
+
+    fn delete(&self, path: &str, recursive: bool) -> Result<()> {
+        let path = self.absolute(path);
+        let mut tree = self.lock();
+
+        if path == "/" {
+            return Err(HdfsError::new(HdfsErrorKind::PermissionDenied, "Cannot delete the root directory"));
+        }
+
+        let node = lookup(&tree, &path)?;
+        if node.kind == ObjectKind::Directory {
+            let descendants: Vec<String> = tree.keys()
+                .filter(|key| is_descendant(key, &path))
+                .cloned()
+                .collect();
+
+            if !descendants.is_empty() && !recursive {
+                return Err(HdfsError::new(HdfsErrorKind::Other,
+                    format!("Directory {:?} is not empty", path)));
+            }
+            for descendant in descendants {
+                tree.remove(&descendant);
+            }
+        }
+
+        tree.remove(&path);
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &str) -> Result<()> {
+        let path = self.absolute(path);
+        let mut tree = self.lock();
+
+        match tree.get(&path) {
+            Some(node) if node.kind == ObjectKind::File => Err(HdfsError::new(HdfsErrorKind::AlreadyExists,
+                format!("{:?} already exists as a file", path))),
+            Some(_) => Ok(()),
+            None => {
+                create_parents(&mut tree, &path)?;
+                tree.insert(path, Node::directory());
+                Ok(())
+            }
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let (from, to) = (self.absolute(from), self.absolute(to));
+        let mut tree = self.lock();
+
+        lookup(&tree, &from)?;
+        if from == "/" || is_descendant(&to, &from) {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Cannot move {:?} into {:?}", from, to)));
+        }
+        if tree.contains_key(&to) {
+            return Err(HdfsError::new(HdfsErrorKind::AlreadyExists,
+                format!("{:?} already exists", to)));
+        }
+        // unlike creating files, renaming does not create missing parents
+        if let Some(dir) = parent(&to) {
+            if lookup(&tree, dir)?.kind == ObjectKind::File {
+                return Err(HdfsError::new(HdfsErrorKind::NotADirectory,
+                    format!("Parent {:?} is not a directory", dir)));
+            }
+        }
+
+        let moved: Vec<String> = tree.keys()
+            .filter(|key| **key == from || is_descendant(key, &from))
+            .cloned()
+            .collect();
+        for key in moved {
+            let node = tree.remove(&key).unwrap();
+            tree.insert([to.as_str(), &key[from.len()..]].concat(), node);
+        }
+        Ok(())
+    }
+
+    fn set_permissions(&self, path: &str, mode: i16) -> Result<()> {
+        self.update(path, |node| node.permissions = mode)
+    }
+
+    fn set_owner(&self, path: &str, owner: Option<&str>, group: Option<&str>) -> Result<()> {
+        self.update(path, |node| {
+            if let Some(owner) = owner {
+                node.owner = owner.to_string();
+            }
+            if let Some(group) = group {
+                node.group = group.to_string();
+            }
+        })
+    }
+
+    fn set_times(&self, path: &str, last_mod: Option<i64>, last_access: Option<i64>) -> Result<()> {
+        self.update(path, |node| {
+            node.last_mod = last_mod.unwrap_or(node.last_mod);
+            node.last_access = last_access.unwrap_or(node.last_access);
+        })
+    }
+
+    fn set_replication(&self, path: &str, replication: i16) -> Result<()> {
+        if self.path_info(path)?.kind == ObjectKind::Directory {
+            return Err(HdfsError::new(HdfsErrorKind::IsADirectory,
+                format!("Directories have no replication: {:?}", path)));
+        }
+        self.update(path, |node| node.replication = replication)
+    }
+
+    fn hosts(&self, path: &str, start: i64, length: i64) -> Result<Vec<Vec<String>>> {
+        let path = self.absolute(path);
+        let tree = self.lock();
+        let node = lookup(&tree, &path)?;
+
+        let file_size = node.data.len() as i64;
+        let end = std::cmp::min(start.saturating_add(length), file_size);
+        if node.kind == ObjectKind::Directory || start < 0 || start >= end {
+            return Ok(Vec::new());
+        }
+
+        let blocks = (end - 1) / node.block_size - start / node.block_size + 1;
+        Ok((0..blocks).map(|_| vec![String::from("localhost")]).collect())
+    }
+
+    fn working_directory(&self) -> Result<String> {
+        Ok(self.working_dir.lock().unwrap_or_else(|err| err.into_inner()).clone())
+    }
+
+    fn set_working_directory(&self, path: &str) -> Result<()> {
+        let path = self.absolute(path);
+        *self.working_dir.lock().unwrap_or_else(|err| err.into_inner()) = path;
+        Ok(())
+    }
+
+    fn capacity(&self) -> Result<i64> {
+        Ok(self.capacity)
+    }
+
+    fn used(&self) -> Result<i64> {
+        let tree = self.lock();
+        Ok(tree.values().map(|node| node.data.len() as i64 * node.replication as i64).sum())
+    }
+
+    fn default_block_size(&self, _path: Option<&str>) -> Result<i64> {
+        Ok(self.block_size)
+    }
+}
+
+
+struct MemoryFile {
+    tree: Arc<Mutex<Tree>>,
+    path: String,
+    position: i64,
+    writable: bool,
+    // everything is "local"
+    bytes_read: u64,
+    max_read_size: usize,
+    max_write_size: usize,
+}
+
+impl MemoryFile {
+
+    fn check_writable(&self) -> Result<()> {
+        match self.writable {
+            true => Ok(()),
+            false => Err(HdfsError::new(HdfsErrorKind::Other,
+                format!("{:?} is open for reading", self.path))),
+        }
+    }
+
+    fn check_readable(&self) -> Result<()> {
+        match self.writable {
+            true => Err(HdfsError::new(HdfsErrorKind::Other,
+                format!("{:?} is open for writing", self.path))),
+            false => Ok(()),
+        }
+    }
+
+    fn read_at(&self, position: i64, buf: &mut [u8]) -> Result<usize> {
+        self.check_readable()?;
+
+        let tree = lock_tree(&self.tree);
+        let data = &lookup(&tree, &self.path)?.data;
+
+        let start = std::cmp::min(std::cmp::max(position, 0) as usize, data.len());
+        let read_bytes = std::cmp::min(std::cmp::min(buf.len(), data.len() - start), self.max_read_size);
+        buf[..read_bytes].copy_from_slice(&data[start..start + read_bytes]);
+
+        Ok(read_bytes)
+    }
+}
+
+impl BackendFile for MemoryFile {
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read_bytes = self.read_at(self.position, buf)?;
+        self.position += read_bytes as i64;
+        self.bytes_read += read_bytes as u64;
+        Ok(read_bytes)
+    }
+
+    fn pread(&mut self, position: i64, buf: &mut [u8]) -> Result<usize> {
+        let read_bytes = self.read_at(position, buf)?;
+        self.bytes_read += read_bytes as u64;
+        Ok(read_bytes)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.check_writable()?;
+        let buf = &buf[..std::cmp::min(buf.len(), self.max_write_size)];
+
+        let mut tree = lock_tree(&self.tree);
+        let node = tree.get_mut(&self.path).ok_or_else(|| not_found(&self.path))?;
+        node.data.extend_from_slice(buf);
+        node.last_mod = now();
+
+        self.position += buf.len() as i64;
+        Ok(buf.len())
+    }
+
+    fn seek(&mut self, position: i64) -> Result<()> {
+        if self.writable {
+            return Err(HdfsError::new(HdfsErrorKind::Unsupported,
+                format!("Cannot seek {:?} while writing", self.path)));
+        }
+
+        let tree = lock_tree(&self.tree);
+        let size = lookup(&tree, &self.path)?.data.len() as i64;
+        if position < 0 || position > size {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Cannot seek {:?} to {}", self.path, position)));
+        }
+
+        self.position = position;
+        Ok(())
+    }
+
+    fn tell(&mut self) -> Result<i64> {
+        Ok(self.position)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    // writes are visible and "durable" as soon as they are made
+    fn hflush(&mut self) -> Result<()> {
+        self.check_writable()
+    }
+
+    fn hsync(&mut self) -> Result<()> {
+        self.check_writable()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn read_statistics(&self) -> Result<ReadStatistics> {
+        self.check_readable()?;
+        Ok(ReadStatistics {
+            total_bytes: self.bytes_read,
+            local_bytes: self.bytes_read,
+            ..ReadStatistics::default()
+        })
+    }
+
+    fn clear_read_statistics(&mut self) -> Result<()> {
+        self.check_readable()?;
+        self.bytes_read = 0;
+        Ok(())
+    }
+}
+
+
+impl Node {
+
+    fn directory() -> Node {
+        let now = now();
+        Node {
+            kind: ObjectKind::Directory,
+            data: Vec::new(),
+            last_mod: now,
+            last_access: now,
+            replication: 0,
+            block_size: 0,
+            owner: String::from("hdfs"),
+            group: String::from("supergroup"),
+            permissions: 0o755,
+        }
+    }
+
+    fn info(&self, path: &str) -> FileInfo {
+        FileInfo {
+            kind: self.kind,
+            name: path.to_string(),
+            last_mod: self.last_mod,
+            size: self.data.len() as i64,
+            replication: self.replication,
+            block_size: self.block_size,
+            owner: self.owner.clone(),
+            group: self.group.clone(),
+            permissions: self.permissions,
+            last_access: self.last_access,
+        }
+    }
+}
+
+fn lock_tree(tree: &Mutex<Tree>) -> MutexGuard<'_, Tree> {
+    // every mutation leaves the tree consistent, so a panic elsewhere while
+    // holding the lock does not invalidate it
+    tree.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn lookup<'a>(tree: &'a Tree, path: &str) -> Result<&'a Node> {
+    tree.get(path).ok_or_else(|| not_found(path))
+}
+
+fn not_found(path: &str) -> HdfsError {
+    HdfsError::new(HdfsErrorKind::NotFound, format!("No such file or directory: {:?}", path))
+}
+
+fn children<'a>(tree: &'a Tree, dir: &'a str) -> impl Iterator<Item = (&'a String, &'a Node)> {
+    tree.iter().filter(move |(key, _)| parent(key) == Some(dir))
+}
+
+fn create_parents(tree: &mut Tree, path: &str) -> Result<()> {
+    let mut ancestors = Vec::new();
+    let mut current = parent(path);
+    while let Some(dir) = current {
+        ancestors.push(dir);
+        current = parent(dir);
+    }
+
+    for dir in ancestors.into_iter().rev() {
+        match tree.get(dir) {
+            Some(node) if node.kind == ObjectKind::File => {
+                return Err(HdfsError::new(HdfsErrorKind::NotADirectory,
+                    format!("Parent {:?} is not a directory", dir)));
+            }
+            Some(_) => {},
+            None => { tree.insert(dir.to_string(), Node::directory()); },
+        }
+    }
+    Ok(())
+}
+
+fn is_descendant(path: &str, dir: &str) -> bool {
+    path.len() > dir.len() && path.starts_with(dir) && (dir == "/" || path.as_bytes()[dir.len()] == b'/')
+}
+
+fn parent(path: &str) -> Option<&str> {
+    match path.rfind('/') {
+        _ if path == "/" => None,
+        Some(0) => Some("/"),
+        Some(idx) => Some(&path[..idx]),
+        None => None,
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+// A filesystem over a fresh `MemoryBackend` holding `content` at `/f`.
+#[cfg(test)]
+pub(crate) fn fs_with(content: &[u8]) -> crate::HdfsFileSystem {
+    use std::io::Write;
+
+    let fs = crate::HdfsFileSystem::with_backend("memory", MemoryBackend::new());
+    fs.create("/f").unwrap().write_all(content).unwrap();
+    fs
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{O_CREAT, O_WRONLY};
+
+    fn write_file(backend: &MemoryBackend, path: &str, content: &[u8]) {
+        let mut file = backend.open(path, &OpenRequest::new((O_WRONLY | O_CREAT) as i32)).unwrap();
+        file.write(content).unwrap();
+        file.close().unwrap();
+    }
+
+    #[test]
+    fn create_makes_parents() {
+        let backend = MemoryBackend::new();
+        write_file(&backend, "/warehouse/t/part-0", b"abc");
+
+        assert_eq!(backend.path_info("/warehouse").unwrap().kind, ObjectKind::Directory);
+        assert_eq!(backend.path_info("/warehouse/t/part-0").unwrap().size, 3);
+        assert_eq!(backend.list("/warehouse").unwrap().len(), 1);
+        assert_eq!(backend.list("/").unwrap()[0].name, "/warehouse");
+    }
+
+    #[test]
+    fn read_write_and_append() {
+        let backend = MemoryBackend::new();
+        write_file(&backend, "/f", b"hello");
+
+        let mut appender = backend.open("/f", &OpenRequest::new((O_WRONLY | O_APPEND) as i32)).unwrap();
+        appender.write(b" world").unwrap();
+
+        let mut reader = backend.open("/f", &OpenRequest::new(O_RDONLY as i32)).unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 8);
+        assert_eq!(&buf, b"hello wo");
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(reader.pread(6, &mut buf[..5]).unwrap(), 5);
+        assert_eq!(&buf[..5], b"world");
+        assert_eq!(reader.pread(100, &mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn delete_requires_recursive_for_non_empty_dirs() {
+        let backend = MemoryBackend::new();
+        write_file(&backend, "/d/e/f", b"");
+
+        assert!(backend.delete("/d", false).is_err());
+        backend.delete("/d", true).unwrap();
+        assert!(!backend.exists("/d/e").unwrap());
+        assert_eq!(backend.delete("/d", true).unwrap_err().kind(), HdfsErrorKind::NotFound);
+        assert!(backend.delete("/", true).is_err());
+    }
+
+    #[test]
+    fn rename_moves_subtrees() {
+        let backend = MemoryBackend::new();
+        write_file(&backend, "/a/b/c", b"abc");
+        backend.create_dir("/x/y").unwrap();
+
+        backend.rename("/a", "/x/y/z").unwrap();
+        assert!(!backend.exists("/a").unwrap());
+        assert_eq!(backend.path_info("/x/y/z/b/c").unwrap().size, 3);
+
+        assert_eq!(backend.rename("/x", "/x/y/w").unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+        assert_eq!(backend.rename("/x/y/z", "/x/y").unwrap_err().kind(), HdfsErrorKind::AlreadyExists);
+        assert_eq!(backend.rename("/x", "/missing/x").unwrap_err().kind(), HdfsErrorKind::NotFound);
+        assert_eq!(backend.create_dir("/x/y/z/b/c").unwrap_err().kind(), HdfsErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn hosts_per_block() {
+        let backend = MemoryBackend::new().with_block_size(4);
+        write_file(&backend, "/f", b"0123456789");
+
+        assert_eq!(backend.hosts("/f", 0, 10).unwrap().len(), 3);
+        assert_eq!(backend.hosts("/f", 3, 2).unwrap().len(), 2);
+        assert_eq!(backend.hosts("/f", 8, 100).unwrap().len(), 1);
+        assert!(backend.hosts("/f", 10, 1).unwrap().is_empty());
+
+        let backend = MemoryBackend::new().with_block_size(0);
+        write_file(&backend, "/f", b"0123456789");
+        assert_eq!(backend.path_info("/f").unwrap().block_size, DEFAULT_BLOCK_SIZE);
+        assert_eq!(backend.hosts("/f", 0, 10).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::*;
+use crate::backend::LocalBackend;
+use crate::error::Result;
+use crate::hdfs_fs::to_cstring;
+
+
+// Connection parameters for an `HdfsFileSystem`, mirroring libhdfs'
+// `hdfsBuilder`.
+//
+//     let fs = HdfsFileSystem::builder()
+//         .name_node("nn.example.com")
+//         .port(8020)
+//         .user("etl")
+//         .conf("dfs.client.read.shortcircuit", "true")
+//         .connect()?;
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HdfsFileSystemBuilder {
+    name_node: String,
+    port: Option<u16>,
+    user: Option<String>,
+    kerb_ticket_cache_path: Option<String>,
+    force_new_instance: bool,
+    conf: Vec<(String, String)>,
+}
+
+impl Default for HdfsFileSystemBuilder {
+    fn default() -> Self {
+        HdfsFileSystemBuilder {
+            name_node: String::from("default"),
+            port: None,
+            user: None,
+            kerb_ticket_cache_path: None,
+            force_new_instance: false,
+            conf: Vec::new(),
+        }
+    }
+}
+
+impl HdfsFileSystemBuilder {
+
+    pub fn new() -> HdfsFileSystemBuilder {
+        HdfsFileSystemBuilder::default()
+    }
+
+    // The namenode host, `default` for the one from the XML configuration,
+    // or a URI such as `hdfs://nn:8020` (in which case `port` must not be set).
+    // `file:///some/dir` serves paths from a local directory instead, see
+    // `LocalBackend`.
+    pub fn name_node<S: Into<String>>(mut self, name_node: S) -> Self {
+        self.name_node = name_node.into();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn user<S: Into<String>>(mut self, user: S) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    pub fn kerb_ticket_cache_path<S: Into<String>>(mut self, path: S) -> Self {
+        self.kerb_ticket_cache_path = Some(path.into());
+        self
+    }
+
+    // Bypasses the JVM-side `FileSystem` cache so the connection is not
+    // shared with (or closed by) other users of the same namenode.
+    pub fn force_new_instance(mut self, force: bool) -> Self {
+        self.force_new_instance = force;
+        self
+    }
+
+    // Sets an arbitrary Hadoop configuration entry for this connection.
+    pub fn conf<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.conf.push((key.into(), value.into()));
+        self
+    }
+
+    // What `FileSystemCache` keys connections by: the same settings with the
+    // conf sorted (the last value of a key wins, as in Hadoop), forcing a new
+    // instance for caches whose evictions disconnect handles.
+    pub(crate) fn cache_key(&self, force_new_instance: bool) -> HdfsFileSystemBuilder {
+        let mut conf: Vec<(String, String)> = Vec::new();
+        for (key, value) in &self.conf {
+            conf.retain(|(other, _)| other != key);
+            conf.push((key.clone(), value.clone()));
+        }
+        conf.sort();
+
+        HdfsFileSystemBuilder { force_new_instance: self.force_new_instance || force_new_instance, conf, ..self.clone() }
+    }
+
+    // A human readable `host[:port]` for messages.
+    fn display_name(&self) -> String {
+        match self.port {
+            Some(port) => format!("{}:{}", self.name_node, port),
+            None => self.name_node.clone(),
+        }
+    }
+
+    pub fn connect(&self) -> Result<HdfsFileSystem> {
+        if let Some(root) = self.name_node.strip_prefix("file://") {
+            let root = if root.is_empty() { "/" } else { root };
+            return Ok(HdfsFileSystem::with_backend(self.name_node.clone(), LocalBackend::new(root)?));
+        }
+
+        // libhdfs only shallow-copies the strings it is given, so every
+        // CString has to outlive `hdfsBuilderConnect`. Converting them all
+        // up front also means a bad argument never leaks a native builder.
+        let name_node = to_cstring(&self.name_node)?;
+        let user = self.user.as_ref().map(|user| to_cstring(user)).transpose()?;
+        let kerb_ticket_cache_path = self.kerb_ticket_cache_path.as_ref()
+            .map(|path| to_cstring(path)).transpose()?;
+        let conf = self.conf.iter()
+            .map(|(key, value)| Ok((to_cstring(key)?, to_cstring(value)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let bld = unsafe { hdfsNewBuilder() };
+        if bld.is_null() {
+            return Err(HdfsError::last_native("Failed to allocate hdfs builder"));
+        }
+
+        unsafe {
+            hdfsBuilderSetNameNode(bld, name_node.as_ptr());
+            if let Some(port) = self.port {
+                hdfsBuilderSetNameNodePort(bld, port);
+            }
+            if let Some(user) = &user {
+                hdfsBuilderSetUserName(bld, user.as_ptr());
+            }
+            if let Some(path) = &kerb_ticket_cache_path {
+                hdfsBuilderSetKerbTicketCachePath(bld, path.as_ptr());
+            }
+            if self.force_new_instance {
+                hdfsBuilderSetForceNewInstance(bld);
+            }
+        }
+
+        for (key, value) in &conf {
+            let result = unsafe {
+                hdfsBuilderConfSetStr(bld, key.as_ptr(), value.as_ptr())
+            };
+            if result != 0 {
+                let err = HdfsError::last_native(format!("Failed to set conf {:?}", key));
+                unsafe { hdfsFreeBuilder(bld); }
+                return Err(err);
+            }
+        }
+
+        // frees the builder whether or not the connection succeeds
+        let fs = unsafe { hdfsBuilderConnect(bld) };
+
+        match fs.is_null() {
+            false => Ok(HdfsFileSystem::from_raw(self.display_name(), self.user.clone(), fs)),
+            true => {
+                let err = HdfsError::last_native(format!("Failed to connect {}", self.display_name()));
+                match err.kind() {
+                    HdfsErrorKind::Other => Err(err.with_kind(HdfsErrorKind::NotConnected)),
+                    _ => Err(err),
+                }
+            }
+        }
+    }
+}
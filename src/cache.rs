@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, TryLockError};
+use std::time::{Duration, Instant};
+
+use crate::*;
+use crate::error::Result;
+
+
+type Connector = dyn Fn(&HdfsFileSystemBuilder) -> Result<HdfsFileSystem> + Send + Sync;
+// `None` until connected; held locked while connecting.
+type Slot = Arc<Mutex<Option<HdfsFileSystem>>>;
+
+// Shared filesystem handles for services acting for many users, keyed by
+// namenode, port, user, ticket cache and conf overrides:
+//
+//     let cache = FileSystemCache::new()
+//         .max_handles(64)
+//         .idle_timeout(Duration::from_secs(300));
+//     let fs = cache.get(&HdfsFileSystem::builder().name_node("nn").port(8020).user("etl"))?;
+//
+// When handles can be evicted, each is its own JVM `FileSystem` (like
+// `hdfsConnectAsUserNewInstance`), so evicting one never closes a handle used
+// elsewhere. Dropping a handle from the cache only disconnects it once the
+// last clone handed out is dropped too; handles that are still held are
+// never evicted.
+pub struct FileSystemCache {
+    handles: Mutex<HashMap<HdfsFileSystemBuilder, CachedFs>>,
+    max_handles: usize,
+    idle_timeout: Option<Duration>,
+    connect: Box<Connector>,
+}
+
+struct CachedFs {
+    slot: Slot,
+    last_used: Instant,
+}
+
+impl CachedFs {
+
+    // Whether the handle is connected and the cache holds the only
+    // reference to it.
+    fn is_unused(&self) -> bool {
+        let fs = match self.slot.try_lock() {
+            Ok(fs) => fs,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => return false,
+        };
+        fs.as_ref().is_some_and(|fs| fs.handle_count() == 1)
+    }
+}
+
+// The cache behind `HdfsFileSystem::connect_default` and `from_uri`; it
+// neither evicts nor limits handles.
+static SHARED: OnceLock<FileSystemCache> = OnceLock::new();
+
+pub(crate) fn shared() -> &'static FileSystemCache {
+    SHARED.get_or_init(FileSystemCache::new)
+}
+
+impl Default for FileSystemCache {
+    fn default() -> Self {
+        FileSystemCache::new()
+    }
+}
+
+impl FileSystemCache {
+
+    pub fn new() -> FileSystemCache {
+        FileSystemCache {
+            handles: Mutex::new(HashMap::new()),
+            max_handles: usize::MAX,
+            idle_timeout: None,
+            connect: Box::new(|builder| builder.connect()),
+        }
+    }
+
+    // At most this many handles are kept; when a new one is needed, the
+    // least recently used unused handle is evicted, and `get` fails with
+    // `QuotaExceeded` if all of them are in use.
+    pub fn max_handles(mut self, max_handles: usize) -> Self {
+        self.max_handles = std::cmp::max(max_handles, 1);
+        self
+    }
+
+    // Unused handles not handed out for this long are evicted by the next
+    // `get` or `evict_idle`.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    // Replaces `HdfsFileSystemBuilder::connect`, e.g. to serve every key
+    // from a `MemoryBackend` in tests.
+    pub fn connector<F>(mut self, connect: F) -> Self
+        where F: Fn(&HdfsFileSystemBuilder) -> Result<HdfsFileSystem> + Send + Sync + 'static {
+        self.connect = Box::new(connect);
+        self
+    }
+
+    // The cached handle for these settings, connecting on first use. Only
+    // callers asking for the same settings wait for a connection in
+    // progress, and only successful connections are remembered.
+    pub fn get(&self, builder: &HdfsFileSystemBuilder) -> Result<HdfsFileSystem> {
+        let key = self.key(builder);
+        let slot = self.slot(&key)?;
+
+        let mut fs = slot.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(fs) = fs.as_ref() {
+            return Ok(fs.clone());
+        }
+
+        match (self.connect)(&key) {
+            Ok(connected) => {
+                *fs = Some(connected.clone());
+                Ok(connected)
+            }
+            Err(err) => {
+                let mut handles = self.lock();
+                if handles.get(&key).is_some_and(|cached| Arc::ptr_eq(&cached.slot, &slot)) {
+                    handles.remove(&key);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    // Caches that never evict keep the JVM's own shared instance.
+    fn key(&self, builder: &HdfsFileSystemBuilder) -> HdfsFileSystemBuilder {
+        builder.cache_key(self.max_handles != usize::MAX || self.idle_timeout.is_some())
+    }
+
+    // The slot for `key`, making room for a new one if needed.
+    fn slot(&self, key: &HdfsFileSystemBuilder) -> Result<Slot> {
+        let mut handles = self.lock();
+        let now = Instant::now();
+
+        if let Some(cached) = handles.get_mut(key) {
+            cached.last_used = now;
+            return Ok(cached.slot.clone());
+        }
+
+        self.evict_idle_at(&mut handles, now);
+        if handles.len() >= self.max_handles {
+            let least_recently_used = handles.iter()
+                .filter(|(_, cached)| cached.is_unused())
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| key.clone());
+            match least_recently_used {
+                Some(key) => { handles.remove(&key); },
+                None => return Err(HdfsError::new(HdfsErrorKind::QuotaExceeded,
+                    format!("All {} cached filesystem handles are in use", handles.len()))),
+            }
+        }
+
+        let slot = Slot::default();
+        handles.insert(key.clone(), CachedFs { slot: slot.clone(), last_used: now });
+        Ok(slot)
+    }
+
+    // Evicts the handles idle for longer than `idle_timeout`, returning how
+    // many were evicted.
+    pub fn evict_idle(&self) -> usize {
+        self.evict_idle_at(&mut self.lock(), Instant::now())
+    }
+
+    fn evict_idle_at(&self, handles: &mut HashMap<HdfsFileSystemBuilder, CachedFs>, now: Instant) -> usize {
+        let idle_timeout = match self.idle_timeout {
+            Some(idle_timeout) => idle_timeout,
+            None => return 0,
+        };
+
+        let before = handles.len();
+        handles.retain(|_, cached| !cached.is_unused() || now.duration_since(cached.last_used) < idle_timeout);
+        before - handles.len()
+    }
+
+    // Forgets every handle, in use or not; unused ones are disconnected
+    // right away, the others once their last clone is dropped.
+    pub fn close_all(&self) {
+        self.lock().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<HdfsFileSystemBuilder, CachedFs>> {
+        // a poisoned lock only means another thread panicked while holding
+        // it; the map itself is still consistent
+        self.handles.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl std::fmt::Debug for FileSystemCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileSystemCache")
+            .field("handles", &self.len())
+            .field("max_handles", &self.max_handles)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use crate::backend::MemoryBackend;
+
+    fn memory_cache(connects: &Arc<AtomicUsize>) -> FileSystemCache {
+        let connects = connects.clone();
+        FileSystemCache::new().connector(move |builder| {
+            connects.fetch_add(1, Ordering::SeqCst);
+            Ok(HdfsFileSystem::with_backend(format!("{:?}", builder), MemoryBackend::new()))
+        })
+    }
+
+    fn as_user(user: &str) -> HdfsFileSystemBuilder {
+        HdfsFileSystem::builder().name_node("nn").port(8020).user(user)
+    }
+
+    #[test]
+    fn shares_handles_per_key() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let cache = memory_cache(&connects);
+
+        let fs = cache.get(&as_user("etl")).unwrap();
+        fs.create("/f").unwrap();
+        assert!(cache.get(&as_user("etl")).unwrap().exists("/f").unwrap());
+        assert!(!cache.get(&as_user("hive")).unwrap().exists("/f").unwrap());
+
+        // conf overrides are part of the key, in any order
+        let conf = |first: (&str, &str), second: (&str, &str)| as_user("etl").conf(first.0, first.1).conf(second.0, second.1);
+        cache.get(&conf(("a", "1"), ("b", "2"))).unwrap();
+        cache.get(&conf(("b", "2"), ("a", "1"))).unwrap();
+        cache.get(&conf(("a", "0"), ("b", "2")).conf("a", "1")).unwrap();
+        assert_eq!((cache.len(), connects.load(Ordering::SeqCst)), (3, 3));
+
+        cache.close_all();
+        assert!(cache.is_empty());
+        assert!(fs.exists("/f").unwrap());
+        assert!(!cache.get(&as_user("etl")).unwrap().exists("/f").unwrap());
+    }
+
+    #[test]
+    fn only_evicting_caches_force_new_instances() {
+        assert_eq!(shared().key(&as_user("etl")), as_user("etl"));
+        assert_eq!(FileSystemCache::new().idle_timeout(Duration::from_secs(1)).key(&as_user("etl")),
+            as_user("etl").force_new_instance(true));
+        assert_eq!(FileSystemCache::new().max_handles(8).key(&as_user("etl")), as_user("etl").force_new_instance(true));
+        assert_eq!(shared().key(&as_user("etl").force_new_instance(true)), as_user("etl").force_new_instance(true));
+    }
+
+    #[test]
+    fn evicts_unused_handles() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let cache = memory_cache(&connects).max_handles(2).idle_timeout(Duration::from_millis(50));
+
+        let etl = cache.get(&as_user("etl")).unwrap();
+        cache.get(&as_user("hive")).unwrap();
+        // `hive` is the only unused handle
+        cache.get(&as_user("spark")).unwrap();
+        assert_eq!(cache.len(), 2);
+        cache.get(&as_user("etl")).unwrap();
+        assert_eq!(connects.load(Ordering::SeqCst), 3);
+
+        let spark = cache.get(&as_user("spark")).unwrap();
+        let err = cache.get(&as_user("hive")).unwrap_err();
+        assert_eq!(err.kind(), HdfsErrorKind::QuotaExceeded);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.evict_idle(), 0);
+        drop(etl);
+        assert_eq!(cache.evict_idle(), 1);
+        drop(spark);
+        cache.get(&as_user("hive")).unwrap();
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn slow_connections_only_block_their_key() {
+        let (connects, released) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicBool::new(false)));
+        let cache = {
+            let (connects, released) = (connects.clone(), released.clone());
+            FileSystemCache::new().connector(move |builder| {
+                if format!("{:?}", builder).contains("slow") {
+                    connects.fetch_add(1, Ordering::SeqCst);
+                    while !released.load(Ordering::SeqCst) {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                }
+                Ok(HdfsFileSystem::with_backend("memory", MemoryBackend::new()))
+            })
+        };
+
+        std::thread::scope(|scope| {
+            let slow: Vec<_> = (0..2).map(|_| scope.spawn(|| cache.get(&as_user("slow")).unwrap())).collect();
+            while connects.load(Ordering::SeqCst) == 0 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            cache.get(&as_user("fast")).unwrap();
+            assert_eq!((cache.len(), cache.evict_idle()), (2, 0));
+
+            released.store(true, Ordering::SeqCst);
+            let slow: Vec<_> = slow.into_iter().map(|handle| handle.join().unwrap()).collect();
+            slow[0].create("/f").unwrap();
+            assert!(slow[1].exists("/f").unwrap());
+        });
+        assert_eq!(connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn failed_connections_are_not_cached() {
+        let cache = FileSystemCache::new().connector(|_| Err(HdfsError::new(HdfsErrorKind::NotConnected, "down")));
+        assert_eq!(cache.get(&as_user("etl")).unwrap_err().kind(), HdfsErrorKind::NotConnected);
+        assert!(cache.is_empty());
+    }
+}
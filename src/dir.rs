@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use crate::*;
+use crate::backend::FileInfo;
+use crate::error::Result;
+
+
+// The entries of a directory, as returned by `HdfsFileSystem::read_dir`.
+//
+// The listing is fully buffered: `read_dir` fetches every entry up front, as
+// `hdfsListDirectory` does, and the iterator only turns them into
+// `DirEntry`s as it advances. Very large directories are held in memory
+// whole.
+pub struct ReadDir {
+    fs: HdfsFileSystem,
+    dir: PathBuf,
+    entries: std::vec::IntoIter<FileInfo>,
+}
+
+impl ReadDir {
+
+    pub(crate) fn new(fs: HdfsFileSystem, dir: PathBuf, entries: Vec<FileInfo>) -> ReadDir {
+        ReadDir { fs, dir, entries: entries.into_iter() }
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Result<DirEntry>> {
+        let info = self.entries.next()?;
+
+        // `name` is fully qualified, e.g. `hdfs://nn:8020/dir/file`
+        let file_name = match info.name.rsplit('/').find(|component| !component.is_empty()) {
+            Some(file_name) => file_name.to_string(),
+            None => return Some(Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Unexpected entry {:?} in {:?}", info.name, self.dir)))),
+        };
+
+        let path = self.dir.join(&file_name);
+        Some(Ok(DirEntry::new(self.fs.clone(), path, file_name, info.into(), 1)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl std::fmt::Debug for ReadDir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadDir")
+            .field("dir", &self.dir)
+            .field("remaining", &self.entries.len())
+            .finish()
+    }
+}
+
+
+// One entry of a `ReadDir`. The metadata is the one returned by the listing
+// and is not refreshed.
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    fs: HdfsFileSystem,
+    path: PathBuf,
+    file_name: String,
+    metadata: Metadata,
+    pub(crate) depth: usize,
+}
+
+impl DirEntry {
+
+    pub(crate) fn new(fs: HdfsFileSystem, path: PathBuf, file_name: String, metadata: Metadata, depth: usize) -> DirEntry {
+        DirEntry { fs, path, file_name, metadata, depth }
+    }
+
+    // The listed directory joined with `file_name`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.metadata.file_type()
+    }
+
+    // How far below the listed directory the entry is: 1 for `read_dir`
+    // entries and for the children of a `WalkDir` root. Glob matches count
+    // the components of their whole path.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Opens the entry for reading.
+    pub fn open(&self) -> Result<HdfsFile> {
+        self.fs.open(self.path.clone())
+    }
+}
@@ -0,0 +1,231 @@
+use std::ffi::CStr;
+use std::fmt;
+
+use crate::*;
+
+
+pub type Result<T> = std::result::Result<T, HdfsError>;
+
+
+// Broad classes of failure, derived from the `errno` libhdfs reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HdfsErrorKind {
+    NotFound,
+    PermissionDenied,
+    AlreadyExists,
+    NotADirectory,
+    IsADirectory,
+    QuotaExceeded,
+    InvalidInput,
+    NotConnected,
+    Unsupported,
+    Other,
+}
+
+impl HdfsErrorKind {
+
+    // libhdfs translates the Java exceptions it sees into these errno values,
+    // e.g. `FileNotFoundException` into ENOENT and `DSQuotaExceededException`
+    // into EDQUOT.
+    pub fn from_errno(errno: i32) -> HdfsErrorKind {
+        match errno as u32 {
+            ENOENT => HdfsErrorKind::NotFound,
+            EACCES | EPERM => HdfsErrorKind::PermissionDenied,
+            EEXIST => HdfsErrorKind::AlreadyExists,
+            ENOTDIR => HdfsErrorKind::NotADirectory,
+            EISDIR => HdfsErrorKind::IsADirectory,
+            EDQUOT => HdfsErrorKind::QuotaExceeded,
+            EINVAL => HdfsErrorKind::InvalidInput,
+            ENOTSUP => HdfsErrorKind::Unsupported,
+            _ => HdfsErrorKind::Other,
+        }
+    }
+
+    pub fn io_kind(self) -> std::io::ErrorKind {
+        match self {
+            HdfsErrorKind::NotFound => std::io::ErrorKind::NotFound,
+            HdfsErrorKind::PermissionDenied => std::io::ErrorKind::PermissionDenied,
+            HdfsErrorKind::AlreadyExists => std::io::ErrorKind::AlreadyExists,
+            HdfsErrorKind::NotADirectory => std::io::ErrorKind::NotADirectory,
+            HdfsErrorKind::IsADirectory => std::io::ErrorKind::IsADirectory,
+            HdfsErrorKind::QuotaExceeded => std::io::ErrorKind::QuotaExceeded,
+            HdfsErrorKind::InvalidInput => std::io::ErrorKind::InvalidInput,
+            HdfsErrorKind::NotConnected => std::io::ErrorKind::NotConnected,
+            HdfsErrorKind::Unsupported => std::io::ErrorKind::Unsupported,
+            HdfsErrorKind::Other => std::io::ErrorKind::Other,
+        }
+    }
+}
+
+
+// An error from the hdfs layer.
+//
+// Errors raised by libhdfs carry the `errno` it set and, when available, the
+// root cause and stack trace of the Java exception behind it. Converting into
+// `std::io::Error` keeps the whole `HdfsError` as the inner error, so it can
+// be recovered with `get_ref()`/`into_inner()` or `HdfsError::from`.
+#[derive(Debug)]
+pub struct HdfsError {
+    kind: HdfsErrorKind,
+    message: String,
+    errno: Option<i32>,
+    root_cause: Option<String>,
+    stack_trace: Option<String>,
+    source: Option<std::io::Error>,
+}
+
+impl HdfsError {
+
+    pub fn new<S: Into<String>>(kind: HdfsErrorKind, message: S) -> HdfsError {
+        HdfsError {
+            kind,
+            message: message.into(),
+            errno: None,
+            root_cause: None,
+            stack_trace: None,
+            source: None,
+        }
+    }
+
+    // Captures `errno` and the pending Java exception of the libhdfs call
+    // that just failed on this thread. Must be called before anything else
+    // talks to libhdfs.
+    pub fn last_native<S: Into<String>>(message: S) -> HdfsError {
+        let errno = std::io::Error::last_os_error().raw_os_error().filter(|errno| *errno != 0);
+        let root_cause = unsafe { copy_c_str(hdfsGetLastExceptionRootCause()) };
+        let stack_trace = unsafe { copy_c_str(hdfsGetLastExceptionStackTrace()) };
+
+        HdfsError {
+            kind: errno.map_or(HdfsErrorKind::Other, HdfsErrorKind::from_errno),
+            message: message.into(),
+            errno,
+            root_cause,
+            stack_trace,
+            source: None,
+        }
+    }
+
+    pub(crate) fn with_kind(mut self, kind: HdfsErrorKind) -> HdfsError {
+        self.kind = kind;
+        self
+    }
+
+    pub fn kind(&self) -> HdfsErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn errno(&self) -> Option<i32> {
+        self.errno
+    }
+
+    // The root cause of the Java exception, e.g.
+    // `org.apache.hadoop.security.AccessControlException: Permission denied: ...`
+    pub fn root_cause(&self) -> Option<&str> {
+        self.root_cause.as_deref()
+    }
+
+    pub fn stack_trace(&self) -> Option<&str> {
+        self.stack_trace.as_deref()
+    }
+}
+
+impl fmt::Display for HdfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if let Some(errno) = self.errno {
+            write!(f, " (errno {})", errno)?;
+        }
+        if let Some(root_cause) = &self.root_cause {
+            write!(f, ": {}", root_cause)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HdfsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_ref().map(|err| err as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl From<HdfsError> for std::io::Error {
+    fn from(err: HdfsError) -> std::io::Error {
+        std::io::Error::new(err.kind.io_kind(), err)
+    }
+}
+
+impl From<std::io::Error> for HdfsError {
+    fn from(err: std::io::Error) -> HdfsError {
+        // undo a previous `HdfsError -> std::io::Error` conversion
+        if err.get_ref().is_some_and(|inner| inner.is::<HdfsError>()) {
+            let inner = err.into_inner().unwrap();
+            return *inner.downcast::<HdfsError>().unwrap();
+        }
+
+        let kind = match err.kind() {
+            std::io::ErrorKind::NotFound => HdfsErrorKind::NotFound,
+            std::io::ErrorKind::PermissionDenied => HdfsErrorKind::PermissionDenied,
+            std::io::ErrorKind::AlreadyExists => HdfsErrorKind::AlreadyExists,
+            std::io::ErrorKind::NotADirectory => HdfsErrorKind::NotADirectory,
+            std::io::ErrorKind::IsADirectory => HdfsErrorKind::IsADirectory,
+            std::io::ErrorKind::QuotaExceeded => HdfsErrorKind::QuotaExceeded,
+            std::io::ErrorKind::InvalidInput => HdfsErrorKind::InvalidInput,
+            std::io::ErrorKind::NotConnected => HdfsErrorKind::NotConnected,
+            std::io::ErrorKind::Unsupported => HdfsErrorKind::Unsupported,
+            _ => HdfsErrorKind::Other,
+        };
+
+        HdfsError {
+            kind,
+            message: err.to_string(),
+            errno: err.raw_os_error(),
+            root_cause: None,
+            stack_trace: None,
+            source: Some(err),
+        }
+    }
+}
+
+unsafe fn copy_c_str(ptr: *const std::os::raw::c_char) -> Option<String> {
+    match ptr.is_null() {
+        true => None,
+        false => Some(CStr::from_ptr(ptr).to_string_lossy().into_owned()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errno_maps_to_kind() {
+        assert_eq!(HdfsErrorKind::from_errno(ENOENT as i32), HdfsErrorKind::NotFound);
+        assert_eq!(HdfsErrorKind::from_errno(EACCES as i32), HdfsErrorKind::PermissionDenied);
+        assert_eq!(HdfsErrorKind::from_errno(EEXIST as i32), HdfsErrorKind::AlreadyExists);
+        assert_eq!(HdfsErrorKind::from_errno(ENOTDIR as i32), HdfsErrorKind::NotADirectory);
+        assert_eq!(HdfsErrorKind::from_errno(EISDIR as i32), HdfsErrorKind::IsADirectory);
+        assert_eq!(HdfsErrorKind::from_errno(EDQUOT as i32), HdfsErrorKind::QuotaExceeded);
+        assert_eq!(HdfsErrorKind::from_errno(EIO as i32), HdfsErrorKind::Other);
+    }
+
+    #[test]
+    fn io_error_round_trip_is_lossless() {
+        let mut err = HdfsError::new(HdfsErrorKind::QuotaExceeded, "Failed to write /quota/file");
+        err.errno = Some(EDQUOT as i32);
+        err.root_cause = Some(String::from("DSQuotaExceededException: quota exceeded"));
+
+        let io_err = std::io::Error::from(err);
+        assert_eq!(io_err.kind(), std::io::ErrorKind::QuotaExceeded);
+        assert!(io_err.to_string().contains("DSQuotaExceededException"));
+
+        let err = HdfsError::from(io_err);
+        assert_eq!(err.kind(), HdfsErrorKind::QuotaExceeded);
+        assert_eq!(err.errno(), Some(EDQUOT as i32));
+        assert_eq!(err.root_cause(), Some("DSQuotaExceededException: quota exceeded"));
+    }
+}
@@ -0,0 +1,543 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::*;
+use crate::error::Result;
+
+
+// Expands a glob pattern the way `hdfs dfs -ls` does, see `Glob`.
+pub fn glob(fs: &HdfsFileSystem, pattern: &str) -> Result<Vec<DirEntry>> {
+    Glob::new(pattern).expand(fs)
+}
+
+
+// A glob pattern over HDFS paths, following Hadoop's `GlobPattern`:
+//
+//  - `*` matches any run of characters within one path component
+//  - `?` matches any single character
+//  - `[abc]`, `[a-z]` match one character of a class, `[^a]` or `[!a]` one
+//    outside of it
+//  - `{a,b}` matches either alternative; alternatives may nest and contain `/`
+//  - `\` escapes the next character
+//
+// and additionally `**`, which matches any number of whole components
+// (including none).
+//
+// Only the directories a wildcard has to look into are listed; components
+// without wildcards are appended as they are and only checked for existence
+// at the end. Paths that do not exist simply do not match, and matches are
+// returned sorted by path.
+//
+// Like Hadoop, wildcards match hidden names (starting with `.` or `_`) by
+// default; `skip_hidden` leaves them out the way MapReduce input listing
+// does, unless the pattern component itself starts with `.` or `_`.
+#[derive(Clone, Debug)]
+pub struct Glob {
+    pattern: String,
+    skip_hidden: bool,
+}
+
+// A path matched so far; `entry` is known when it came from a listing.
+struct Candidate {
+    path: PathBuf,
+    entry: Option<DirEntry>,
+}
+
+impl Glob {
+
+    pub fn new<S: Into<String>>(pattern: S) -> Glob {
+        Glob { pattern: pattern.into(), skip_hidden: false }
+    }
+
+    pub fn skip_hidden(mut self, skip_hidden: bool) -> Self {
+        self.skip_hidden = skip_hidden;
+        self
+    }
+
+    pub fn expand(&self, fs: &HdfsFileSystem) -> Result<Vec<DirEntry>> {
+        if self.pattern.is_empty() {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput, "Empty glob pattern"));
+        }
+
+        let mut matches = Vec::new();
+        for pattern in expand_braces(&self.pattern)? {
+            matches.extend(self.expand_without_braces(fs, &pattern)?);
+        }
+
+        matches.sort_by(|a, b| a.path().cmp(b.path()));
+        matches.dedup_by(|a, b| a.path() == b.path());
+        Ok(matches)
+    }
+
+    fn expand_without_braces(&self, fs: &HdfsFileSystem, pattern: &str) -> Result<Vec<DirEntry>> {
+        let root = match pattern.starts_with('/') {
+            true => PathBuf::from("/"),
+            false => PathBuf::new(),
+        };
+        let mut candidates = vec![Candidate { path: root, entry: None }];
+
+        for component in pattern.split('/').filter(|component| !component.is_empty()) {
+            if candidates.is_empty() {
+                break;
+            }
+
+            let mut next = Vec::new();
+            if component == "**" {
+                for candidate in candidates {
+                    self.descendants(fs, &candidate.path, &mut next)?;
+                    next.push(candidate);
+                }
+            } else {
+                let tokens = compile(component)?;
+                match literal(&tokens) {
+                    // files listed by `**` or a wildcard have no children
+                    Some(name) => {
+                        next.extend(candidates.into_iter()
+                            .filter(|candidate| candidate.entry.as_ref().is_none_or(|entry| entry.file_type().is_dir()))
+                            .map(|candidate| Candidate {
+                                path: candidate.path.join(&name),
+                                entry: None,
+                            }));
+                    }
+                    None => {
+                        for candidate in candidates {
+                            self.matching_children(fs, &candidate, &tokens, &mut next)?;
+                        }
+                    }
+                }
+            }
+            candidates = next;
+        }
+
+        let mut matches = Vec::new();
+        for candidate in candidates {
+            match candidate.entry {
+                Some(entry) => matches.push(entry),
+                None => match fs.metadata(or_current(&candidate.path)) {
+                    Ok(metadata) => matches.push(entry_at(fs, candidate.path, metadata)),
+                    Err(err) if missing(&err) => {},
+                    Err(err) => return Err(err),
+                },
+            }
+        }
+        Ok(matches)
+    }
+
+    fn matching_children(&self, fs: &HdfsFileSystem, parent: &Candidate, tokens: &[Token],
+                         matches: &mut Vec<Candidate>) -> Result<()> {
+        if parent.entry.as_ref().is_some_and(|entry| !entry.file_type().is_dir()) {
+            return Ok(());
+        }
+
+        let read_dir = match fs.read_dir(or_current(&parent.path)) {
+            Ok(read_dir) => read_dir,
+            Err(err) if missing(&err) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        let skip_hidden = self.skip_hidden && !matches!(tokens.first(), Some(Token::Char('.')) | Some(Token::Char('_')));
+        for entry in read_dir {
+            let entry = entry?;
+            let name: Vec<char> = entry.file_name().chars().collect();
+            if (skip_hidden && is_hidden(entry.file_name())) || !matches_tokens(tokens, &name) {
+                continue;
+            }
+
+            let path = parent.path.join(entry.file_name());
+            matches.push(Candidate {
+                entry: Some(entry_at(fs, path.clone(), entry.metadata().clone())),
+                path,
+            });
+        }
+        Ok(())
+    }
+
+    fn descendants(&self, fs: &HdfsFileSystem, dir: &Path, matches: &mut Vec<Candidate>) -> Result<()> {
+        let mut walk = fs.walk_dir(or_current(dir));
+        if self.skip_hidden {
+            walk = walk.filter_entry(|entry| !is_hidden(entry.file_name()));
+        }
+
+        for entry in walk {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) if missing(&err) => continue,
+                Err(err) => return Err(err),
+            };
+
+            // walking `.` yields `./a`, keep the pattern's own form
+            let path = match dir.as_os_str().is_empty() {
+                true => entry.path().strip_prefix(".").unwrap_or(entry.path()).to_path_buf(),
+                false => entry.path().to_path_buf(),
+            };
+            matches.push(Candidate {
+                entry: Some(entry_at(fs, path.clone(), entry.metadata().clone())),
+                path,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn entry_at(fs: &HdfsFileSystem, path: PathBuf, metadata: Metadata) -> DirEntry {
+    let file_name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    let depth = path.components().filter(|component| matches!(component, Component::Normal(_))).count();
+    DirEntry::new(fs.clone(), path, file_name, metadata, depth)
+}
+
+// Relative patterns start from the empty path.
+fn or_current(path: &Path) -> &Path {
+    match path.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => path,
+    }
+}
+
+// Paths that vanish or turn out to be files while expanding do not match.
+fn missing(err: &HdfsError) -> bool {
+    matches!(err.kind(), HdfsErrorKind::NotFound | HdfsErrorKind::NotADirectory)
+}
+
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.') || name.starts_with('_')
+}
+
+
+fn invalid_pattern(message: &str, pattern: &str) -> HdfsError {
+    HdfsError::new(HdfsErrorKind::InvalidInput, format!("{} in glob {:?}", message, pattern))
+}
+
+// Expands the first `{...}` group (and recursively the rest) into one
+// pattern per alternative.
+fn expand_braces(pattern: &str) -> Result<Vec<String>> {
+    let chars: Vec<char> = pattern.chars().collect();
+
+    let mut open = None;
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => {
+                // braces inside a character class are plain characters
+                while i < chars.len() && chars[i] != ']' {
+                    i += 1;
+                }
+            }
+            '{' => {
+                if depth == 0 {
+                    open = Some(i);
+                }
+                depth += 1;
+            }
+            ',' if depth == 1 => alternatives.push(i),
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let open = open.unwrap();
+                    let prefix: String = chars[..open].iter().collect();
+                    let suffix: String = chars[i + 1..].iter().collect();
+
+                    let mut bounds = vec![open];
+                    bounds.extend(alternatives);
+                    bounds.push(i);
+
+                    let mut expanded = Vec::new();
+                    for pair in bounds.windows(2) {
+                        let alternative: String = chars[pair[0] + 1..pair[1]].iter().collect();
+                        expanded.extend(expand_braces(&[prefix.as_str(), &alternative, &suffix].concat())?);
+                    }
+                    return Ok(expanded);
+                }
+            }
+            _ => {},
+        }
+        i += 1;
+    }
+
+    match depth {
+        0 => Ok(vec![pattern.to_string()]),
+        _ => Err(invalid_pattern("Unclosed group", pattern)),
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Char(char),
+    // `?`
+    AnyChar,
+    // `*`
+    AnyRun,
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+// Compiles one brace-free path component.
+fn compile(component: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = component.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => tokens.push(Token::Char(escaped)),
+                None => return Err(invalid_pattern("Dangling escape", component)),
+            },
+            '?' => tokens.push(Token::AnyChar),
+            // consecutive stars match the same as one
+            '*' => if tokens.last() != Some(&Token::AnyRun) {
+                tokens.push(Token::AnyRun);
+            },
+            '[' => {
+                let negated = matches!(chars.peek(), Some('^') | Some('!'));
+                if negated {
+                    chars.next();
+                }
+
+                let mut ranges = Vec::new();
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    let start = match c {
+                        // a leading `]` is part of the class
+                        ']' if !ranges.is_empty() => { closed = true; break; }
+                        '\\' => chars.next().ok_or_else(|| invalid_pattern("Dangling escape", component))?,
+                        _ => c,
+                    };
+                    let mut lookahead = chars.clone();
+                    let end = match (lookahead.next(), lookahead.next()) {
+                        (Some('-'), Some(end)) if end != ']' => {
+                            chars.next();
+                            chars.next();
+                            end
+                        }
+                        _ => start,
+                    };
+                    if end < start {
+                        return Err(invalid_pattern("Invalid character range", component));
+                    }
+                    ranges.push((start, end));
+                }
+                if !closed {
+                    return Err(invalid_pattern("Unclosed character class", component));
+                }
+                tokens.push(Token::Class { negated, ranges });
+            }
+            _ => tokens.push(Token::Char(c)),
+        }
+    }
+    Ok(tokens)
+}
+
+// The component as a plain name, if it has no wildcards.
+fn literal(tokens: &[Token]) -> Option<String> {
+    tokens.iter().map(|token| match token {
+        Token::Char(c) => Some(*c),
+        _ => None,
+    }).collect()
+}
+
+fn matches_tokens(tokens: &[Token], name: &[char]) -> bool {
+    // classic wildcard matching, backtracking to the last `*`
+    let (mut t, mut n) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        let matched = match tokens.get(t) {
+            Some(Token::AnyRun) => {
+                last_star = Some((t, n));
+                t += 1;
+                continue;
+            }
+            Some(Token::Char(c)) => *c == name[n],
+            Some(Token::AnyChar) => true,
+            Some(Token::Class { negated, ranges }) => {
+                ranges.iter().any(|(start, end)| (*start..=*end).contains(&name[n])) != *negated
+            }
+            None => false,
+        };
+
+        if matched {
+            t += 1;
+            n += 1;
+        } else if let Some((star, star_n)) = last_star {
+            t = star + 1;
+            n = star_n + 1;
+            last_star = Some((star, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    tokens[t..].iter().all(|token| *token == Token::AnyRun)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::backend::{BackendFile, FileInfo, FileSystemBackend, MemoryBackend, OpenRequest};
+
+    fn matches_glob(pattern: &str, name: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        matches_tokens(&compile(pattern).unwrap(), &name)
+    }
+
+    fn paths(entries: Vec<DirEntry>) -> Vec<String> {
+        entries.iter().map(|entry| entry.path().to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn component_matching() {
+        assert!(matches_glob("part-*.gz", "part-00000.gz"));
+        assert!(matches_glob("part-*.gz", "part-.gz"));
+        assert!(!matches_glob("part-*.gz", "part-00000.gz.tmp"));
+        assert!(matches_glob("*a*b", "xaxxab"));
+        assert!(matches_glob("2026-10-0?", "2026-10-07"));
+        assert!(!matches_glob("2026-10-0?", "2026-10-17"));
+        assert!(matches_glob("[abc]x", "bx"));
+        assert!(matches_glob("[a-c]x", "cx"));
+        assert!(!matches_glob("[^a-c]x", "cx"));
+        assert!(matches_glob("[!a-c]x", "dx"));
+        assert!(matches_glob("[]]", "]"));
+        assert!(matches_glob("\\*", "*"));
+        assert!(!matches_glob("\\*", "a"));
+        assert!(matches_glob("*", ".hidden"));
+
+        assert_eq!(literal(&compile("a\\*b").unwrap()), Some(String::from("a*b")));
+        assert_eq!(literal(&compile("a*b").unwrap()), None);
+        assert!(compile("[ab").is_err());
+        assert!(compile("[z-a]").is_err());
+        assert!(compile("a\\").is_err());
+    }
+
+    #[test]
+    fn brace_expansion() {
+        assert_eq!(expand_braces("/a/{b,c}/d").unwrap(), vec!["/a/b/d", "/a/c/d"]);
+        assert_eq!(expand_braces("{a,b{c,d}}").unwrap(), vec!["a", "bc", "bd"]);
+        assert_eq!(expand_braces("{x/y,z}").unwrap(), vec!["x/y", "z"]);
+        assert_eq!(expand_braces("[{]\\{").unwrap(), vec!["[{]\\{"]);
+        assert!(expand_braces("/a/{b,c").is_err());
+    }
+
+    fn logs_fs() -> HdfsFileSystem {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new());
+        for day in &["2026-09-30", "2026-10-01", "2026-10-02"] {
+            fs.create(format!("/logs/{}/part-0.gz", day)).unwrap();
+            fs.create(format!("/logs/{}/part-1.gz", day)).unwrap();
+            fs.create(format!("/logs/{}/_SUCCESS", day)).unwrap();
+        }
+        fs.create("/logs/2026-10-01/.part-2.gz.crc").unwrap();
+        fs
+    }
+
+    #[test]
+    fn expands_wildcards() {
+        let fs = logs_fs();
+
+        assert_eq!(paths(glob(&fs, "/logs/2026-10-*/part-*.gz").unwrap()), vec![
+            "/logs/2026-10-01/part-0.gz",
+            "/logs/2026-10-01/part-1.gz",
+            "/logs/2026-10-02/part-0.gz",
+            "/logs/2026-10-02/part-1.gz",
+        ]);
+        assert_eq!(paths(glob(&fs, "/logs/{2026-09-30,2026-10-02}/part-[1]*").unwrap()), vec![
+            "/logs/2026-09-30/part-1.gz",
+            "/logs/2026-10-02/part-1.gz",
+        ]);
+        assert_eq!(glob(&fs, "/logs/*/part-?.gz").unwrap().len(), 6);
+        assert!(glob(&fs, "/logs/2027-*/*").unwrap().is_empty());
+        assert!(glob(&fs, "/logs/2026-10-01/missing").unwrap().is_empty());
+
+        // duplicates from overlapping alternatives are reported once
+        assert_eq!(glob(&fs, "/logs/{2026-10-01,2026-10-0?}/part-0.gz").unwrap().len(), 2);
+
+        let entry = &glob(&fs, "logs/2026-10-01").unwrap()[0];
+        assert_eq!(entry.path(), Path::new("logs/2026-10-01"));
+        assert!(entry.file_type().is_dir());
+        assert_eq!(entry.depth(), 2);
+    }
+
+    #[test]
+    fn recursive_wildcard() {
+        let fs = logs_fs();
+
+        let gz = paths(glob(&fs, "/**/*.gz").unwrap());
+        assert_eq!(gz.len(), 6);
+        assert!(gz.iter().all(|path| path.ends_with(".gz")));
+
+        // `**` also matches no component at all
+        assert_eq!(glob(&fs, "/logs/2026-10-01/**/part-0.gz").unwrap().len(), 1);
+        assert_eq!(glob(&fs, "/logs/**").unwrap().len(), 1 + 3 + 10);
+    }
+
+    #[test]
+    fn hidden_files() {
+        let fs = logs_fs();
+
+        assert_eq!(glob(&fs, "/logs/2026-10-01/*").unwrap().len(), 4);
+
+        let visible = Glob::new("/logs/2026-10-01/*").skip_hidden(true).expand(&fs).unwrap();
+        assert_eq!(paths(visible), vec!["/logs/2026-10-01/part-0.gz", "/logs/2026-10-01/part-1.gz"]);
+
+        let crc = Glob::new("/logs/*/.*.crc").skip_hidden(true).expand(&fs).unwrap();
+        assert_eq!(paths(crc), vec!["/logs/2026-10-01/.part-2.gz.crc"]);
+        assert_eq!(Glob::new("/**/_SUCCESS").skip_hidden(true).expand(&fs).unwrap().len(), 3);
+    }
+
+    struct CountingBackend {
+        inner: MemoryBackend,
+        lists: Arc<AtomicUsize>,
+        stats: Arc<AtomicUsize>,
+    }
+
+    impl FileSystemBackend for CountingBackend {
+        fn exists(&self, path: &str) -> Result<bool> { self.inner.exists(path) }
+        fn open(&self, path: &str, request: &OpenRequest) -> Result<Box<dyn BackendFile>> { self.inner.open(path, request) }
+        fn delete(&self, path: &str, recursive: bool) -> Result<()> { self.inner.delete(path, recursive) }
+        fn hosts(&self, path: &str, start: i64, length: i64) -> Result<Vec<Vec<String>>> { self.inner.hosts(path, start, length) }
+        fn working_directory(&self) -> Result<String> { self.inner.working_directory() }
+        fn set_working_directory(&self, path: &str) -> Result<()> { self.inner.set_working_directory(path) }
+        fn capacity(&self) -> Result<i64> { self.inner.capacity() }
+        fn used(&self) -> Result<i64> { self.inner.used() }
+        fn default_block_size(&self, path: Option<&str>) -> Result<i64> { self.inner.default_block_size(path) }
+        fn create_dir(&self, path: &str) -> Result<()> { self.inner.create_dir(path) }
+        fn rename(&self, from: &str, to: &str) -> Result<()> { self.inner.rename(from, to) }
+        fn set_permissions(&self, path: &str, mode: i16) -> Result<()> { self.inner.set_permissions(path, mode) }
+        fn set_owner(&self, path: &str, owner: Option<&str>, group: Option<&str>) -> Result<()> { self.inner.set_owner(path, owner, group) }
+        fn set_times(&self, path: &str, last_mod: Option<i64>, last_access: Option<i64>) -> Result<()> { self.inner.set_times(path, last_mod, last_access) }
+        fn set_replication(&self, path: &str, replication: i16) -> Result<()> { self.inner.set_replication(path, replication) }
+
+        fn list(&self, path: &str) -> Result<Vec<FileInfo>> {
+            self.lists.fetch_add(1, Ordering::SeqCst);
+            self.inner.list(path)
+        }
+
+        fn path_info(&self, path: &str) -> Result<FileInfo> {
+            self.stats.fetch_add(1, Ordering::SeqCst);
+            self.inner.path_info(path)
+        }
+    }
+
+    #[test]
+    fn lists_only_what_wildcards_need() {
+        let memory = MemoryBackend::new();
+        let (lists, stats) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let fs = HdfsFileSystem::with_backend("memory", CountingBackend { inner: memory, lists: lists.clone(), stats: stats.clone() });
+        for table in 0..10 {
+            fs.create(format!("/warehouse/t{}/dt=1/part-0", table)).unwrap();
+        }
+
+        assert_eq!(glob(&fs, "/warehouse/t3/dt=1/part-*").unwrap().len(), 1);
+        assert_eq!(lists.load(Ordering::SeqCst), 1);
+        assert_eq!(glob(&fs, "/warehouse/t[12]/dt=1/part-0").unwrap().len(), 2);
+        assert_eq!(lists.load(Ordering::SeqCst), 2);
+
+        // after `**` only the directories are checked for the literal name
+        fs.create("/warehouse/t0/_SUCCESS").unwrap();
+        stats.store(0, Ordering::SeqCst);
+        assert_eq!(glob(&fs, "/warehouse/**/_SUCCESS").unwrap().len(), 1);
+        assert_eq!(stats.load(Ordering::SeqCst), 1 + 10 + 10);
+    }
+}
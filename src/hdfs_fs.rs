@@ -1,387 +1,1293 @@
-include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
-
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::{Bound, RangeBounds};
 use std::path::{Path, PathBuf};
-// use std::fs::{OpenOptions};
-use std::ffi::{CStr, CString};
-use libc::c_void;
+use std::ffi::CString;
+use std::sync::Arc;
+use std::time::SystemTime;
 
+use crate::*;
+use crate::backend::{BackendFile, FileSystemBackend, LibHdfsBackend, ObjectKind, OpenRequest};
+use crate::error::Result;
+use crate::metadata::to_epoch_seconds;
+use crate::uri::FsUri;
 
-pub struct HdfsFile {
-    pub name_node: String, 
-    pub path: PathBuf, // not sure it makes sense or not using Path/PathBuf for hdfs 
-    pub read_pos: i64,
-    pub size: i64,
-    pub block_size: i64,
-    fs: Option<hdfsFS>,
-    opened_file: Option<hdfsFile>, 
+
+struct FsShared {
+    name_node: String,
+    // the user a libhdfs handle connected as, `None` for the current one
+    user: Option<String>,
+    backend: Box<dyn FileSystemBackend>,
 }
 
+// A connection to an HDFS cluster, or to any other `FileSystemBackend`.
+//
+// Cloning is cheap: all clones share one backend (and so one native
+// handle), which is disconnected when the last clone is dropped.
+#[derive(Clone)]
+pub struct HdfsFileSystem {
+    inner: Arc<FsShared>,
+}
 
-impl HdfsFile {
+impl HdfsFileSystem {
 
-    // Attempts to open a file in read-only mode.
-    // pub fn init_with_name_node<P:Into<String>, Q: Into<PathBuf>>(name_node: P, 
-    //     path: Q) -> std::io::Result<HdfsFile> {
-    //     let mut reader = HdfsFile {
-    //         name_node: name_node.into(),
-    //         path: path.into(),
-    //         read_pos: 0,
-    //         size: 0,
-    //         block_size: 0,
-    //         fs: None,
-    //         opened_file: None, 
-    //     };
-
-    //     reader.connect().unwrap();
-    //     reader.open_with_flag(O_RDONLY).unwrap();
-    
-    //     Ok(reader)
+    pub fn builder() -> HdfsFileSystemBuilder {
+        HdfsFileSystemBuilder::new()
+    }
 
-    // }
+    // Connects to the given namenode, e.g. `default` or `hdfs://nn:8020`.
+    pub fn connect<S: Into<String>>(name_node: S) -> Result<HdfsFileSystem> {
+        HdfsFileSystem::builder().name_node(name_node).connect()
+    }
+
+    // Wraps an arbitrary backend, e.g. a `MemoryBackend` in tests.
+    pub fn with_backend<S: Into<String>, B: FileSystemBackend + 'static>(name_node: S, backend: B) -> HdfsFileSystem {
+        HdfsFileSystem {
+            inner: Arc::new(FsShared { name_node: name_node.into(), user: None, backend: Box::new(backend) }),
+        }
+    }
+
+    // Takes ownership of a native handle connected as `user`.
+    pub(crate) fn from_raw(name_node: String, user: Option<String>, fs: hdfsFS) -> HdfsFileSystem {
+        let backend = Box::new(unsafe { LibHdfsBackend::from_raw(fs) });
+        HdfsFileSystem { inner: Arc::new(FsShared { name_node, user, backend }) }
+    }
+
+    // Returns the process-wide handle to the `default` namenode, connecting
+    // on first use. The path-only helpers (`HdfsFile::open`, `read_dir`, ...)
+    // all share it.
+    pub fn connect_default() -> Result<HdfsFileSystem> {
+        crate::cache::shared().get(&HdfsFileSystem::builder())
+    }
+
+    // The filesystem a URI such as `hdfs://etl@nn:8020/user/etl` lives on,
+    // connecting as the user in it, if any. Handles are cached per scheme,
+    // user and authority; `file://` URIs use the local filesystem, and bare
+    // paths or `hdfs:///...` the default namenode.
+    pub fn from_uri<S: AsRef<str>>(uri: S) -> Result<HdfsFileSystem> {
+        FsUri::parse(&HdfsPath::new(uri)?)?.connect()
+    }
+
+    // How many clones of this handle are alive.
+    pub(crate) fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.inner)
+    }
+
+    pub fn name_node(&self) -> &str {
+        &self.inner.name_node
+    }
+
+    pub fn backend(&self) -> &dyn FileSystemBackend {
+        &*self.inner.backend
+    }
+
+    // The raw libhdfs handle, if this filesystem is backed by libhdfs. It
+    // stays valid as long as `self` is alive.
+    pub fn as_raw(&self) -> Option<hdfsFS> {
+        self.inner.backend.raw_fs()
+    }
+
+    // The directory relative paths are resolved against; with libhdfs this
+    // starts as the user's home directory, as a fully qualified URI.
+    pub fn working_directory(&self) -> Result<HdfsPath> {
+        HdfsPath::new(self.inner.backend.working_directory()?)
+    }
+
+    // Changes the working directory of this filesystem and all its clones.
+    // A relative `path` is taken from the current working directory; like
+    // `hdfsSetWorkingDirectory`, it is not checked to exist.
+    pub fn set_working_directory<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = self.absolute(path)?;
+        self.inner.backend.set_working_directory(path.as_str())
+    }
+
+    // `path` resolved against the working directory; absolute and fully
+    // qualified paths are only normalized.
+    pub fn absolute<P: AsRef<Path>>(&self, path: P) -> Result<HdfsPath> {
+        let path = HdfsPath::new(check_path(path.as_ref())?)?;
+        match path.is_absolute() || path.scheme().is_some() {
+            true => Ok(path),
+            false => self.working_directory()?.join(path),
+        }
+    }
 
     // Attempts to open a file in read-only mode.
-    pub fn open<P: Into<PathBuf>>(path: P) -> std::io::Result<HdfsFile> {
-        let mut reader = HdfsFile {
-            name_node: String::from("default"), 
-            path: path.into(),
-            read_pos: 0,
-            size: 0,
-            block_size: 0,
-            fs: None,
-            opened_file: None, 
-        };
-    
-        reader.connect().unwrap();
-        reader.open_with_flag(O_RDONLY).unwrap();
-    
+    pub fn open<P: Into<PathBuf>>(&self, path: P) -> Result<HdfsFile> {
+        let mut reader = HdfsFile::unopened(self.clone(), path.into());
+        reader.open_with_flag(O_RDONLY)?;
+
         Ok(reader)
     }
 
     // Opens a file in write mode.
-    pub fn create<P: Into<PathBuf>>(path: P) -> std::io::Result<HdfsFile> {
-        let mut reader = HdfsFile {
-            name_node: String::from("default"), 
-            path: path.into(),
-            read_pos: 0,
-            size: 0,
-            block_size: 0,
-            fs: None,
-            opened_file: None, 
-        };
-    
-        reader.connect().unwrap();
-        reader.open_with_flag(O_WRONLY | O_CREAT).unwrap();
-    
-        Ok(reader)
+    pub fn create<P: Into<PathBuf>>(&self, path: P) -> Result<HdfsFile> {
+        let mut writer = HdfsFile::unopened(self.clone(), path.into());
+        writer.open_with_flag(O_WRONLY | O_CREAT)?;
+
+        Ok(writer)
     }
 
-    
-    // pub fn with_option() -> OpenOptions {
-    //     unimplemented!();
-    // }
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        self.inner.backend.exists(check_path(path.as_ref())?)
+    }
 
-    pub fn from_split<P: Into<PathBuf>>(path: P, start: i64, end: i64) -> HdfsFile {
+    pub fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Metadata> {
+        Ok(self.inner.backend.path_info(check_path(path.as_ref())?)?.into())
+    }
 
-        let reader = HdfsFile {
-            name_node: String::from("default"), 
-            path: path.into(),
-            read_pos: start,
-            size: end,
-            block_size: 0,
-            fs: None,
-            opened_file: None, 
-        };
-    
-        // reader.connect().unwrap();
-        // reader.open_with_flag(O_RDONLY).unwrap();
+    pub fn delete<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.inner.backend.delete(check_path(path.as_ref())?, false)
+    }
 
-        reader
+    // Creates a directory along with any missing parents
+    // (`hdfsCreateDirectory`).
+    pub fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.inner.backend.create_dir(check_path(path.as_ref())?)
+    }
+
+    // Removes a file; directories are refused.
+    pub fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file_path = check_path(path.as_ref())?;
+        if self.inner.backend.path_info(file_path)?.kind == ObjectKind::Directory {
+            return Err(HdfsError::new(HdfsErrorKind::IsADirectory,
+                format!("Is a directory: {:?}", file_path)));
+        }
+        self.inner.backend.delete(file_path, false)
     }
 
-    pub fn get_hosts(&mut self, start: u64, end: u64) -> std::io::Result<Vec<String>> {
-        let fs = self.fs.unwrap();
-        let file_path = self.path.to_string_lossy();
-        let file_path = CString::new(file_path.as_bytes()).unwrap();
-        
-        unsafe {
-            let block_hosts = hdfsGetHosts(fs, file_path.as_ptr(), start as i64, end as i64);
-            let block_count = (0..).take_while(
-                |i| { let arg = block_hosts.offset(*i); !(*arg).is_null() })
-                .count();
-            
-            let mut hosts_strings: Vec<String> = Vec::new();
-            // looks too complicated
-            for i in 0..block_count {
-                let hosts = *(block_hosts.offset(i as isize));
-                let hosts_len = (0..).take_while(
-                    |i| { let arg = hosts.offset(*i); !(*arg).is_null() }
-                ).count();
-
-                let hosts_iter = std::slice::from_raw_parts(hosts, hosts_len).iter();
-                for one_host in hosts_iter {
-                    hosts_strings.push(CStr::from_ptr(*one_host).to_string_lossy().into_owned());
+    // Removes a directory and everything below it; files are refused.
+    pub fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let dir_path = check_path(path.as_ref())?;
+        if self.inner.backend.path_info(dir_path)?.kind != ObjectKind::Directory {
+            return Err(HdfsError::new(HdfsErrorKind::NotADirectory,
+                format!("Not a directory: {:?}", dir_path)));
+        }
+        self.inner.backend.delete(dir_path, true)
+    }
+
+    // Renames a file or directory within this filesystem. `to` must not
+    // exist, while its parent must; plain `hdfsRename` would instead move
+    // `from` into `to` when that is a directory.
+    //
+    // The check is best-effort: a directory created at `to` after it still
+    // receives `from`, as with `hdfsRename`. A rename failing because a file
+    // appeared there is reported as `AlreadyExists`.
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> Result<()> {
+        let (from_path, to_path) = (check_path(from.as_ref())?, check_path(to.as_ref())?);
+        let already_exists = || HdfsError::new(HdfsErrorKind::AlreadyExists,
+            format!("{:?} already exists", to_path));
+        if self.inner.backend.exists(to_path)? {
+            return Err(already_exists());
+        }
+        match self.inner.backend.rename(from_path, to_path) {
+            Err(err) if err.kind() != HdfsErrorKind::AlreadyExists
+                && self.inner.backend.exists(to_path).unwrap_or(false) => Err(already_exists()),
+            result => result,
+        }
+    }
+
+    pub fn set_permissions<P: AsRef<Path>>(&self, path: P, permissions: Permissions) -> Result<()> {
+        self.inner.backend.set_permissions(check_path(path.as_ref())?, permissions.mode() as i16)
+    }
+
+    // Changes the owner and/or the group; `None` leaves it as it is.
+    pub fn set_owner<P: AsRef<Path>>(&self, path: P, owner: Option<&str>, group: Option<&str>) -> Result<()> {
+        self.inner.backend.set_owner(check_path(path.as_ref())?, owner, group)
+    }
+
+    // Sets the modification and/or access time, truncated to whole seconds;
+    // `None` leaves it as it is.
+    pub fn set_times<P: AsRef<Path>>(&self, path: P, modified: Option<SystemTime>, accessed: Option<SystemTime>) -> Result<()> {
+        let modified = modified.map(to_epoch_seconds).transpose()?;
+        let accessed = accessed.map(to_epoch_seconds).transpose()?;
+        self.inner.backend.set_times(check_path(path.as_ref())?, modified, accessed)
+    }
+
+    // Changes the replication of a file; directories have none.
+    pub fn set_replication<P: AsRef<Path>>(&self, path: P, replication: u16) -> Result<()> {
+        let file_path = check_path(path.as_ref())?;
+        if replication == 0 || replication > i16::MAX as u16 {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Invalid replication {} for {:?}", replication, file_path)));
+        }
+
+        match self.inner.backend.set_replication(file_path, replication as i16) {
+            // libhdfs reports directories as missing
+            Err(err) if err.kind() == HdfsErrorKind::NotFound
+                && self.inner.backend.path_info(file_path).is_ok_and(|info| info.kind == ObjectKind::Directory) => {
+                Err(err.with_kind(HdfsErrorKind::IsADirectory))
+            }
+            result => result,
+        }
+    }
+
+    // `set_permissions` for `path` and everything below it.
+    pub fn set_permissions_recursive<P: AsRef<Path>>(&self, path: P, permissions: Permissions) -> Result<()> {
+        self.for_tree(path.as_ref(), false, |path| self.set_permissions(path, permissions))
+    }
+
+    // `set_owner` for `path` and everything below it.
+    pub fn set_owner_recursive<P: AsRef<Path>>(&self, path: P, owner: Option<&str>, group: Option<&str>) -> Result<()> {
+        self.for_tree(path.as_ref(), false, |path| self.set_owner(path, owner, group))
+    }
+
+    // `set_replication` for `path` if it is a file, or for every file below
+    // it.
+    pub fn set_replication_recursive<P: AsRef<Path>>(&self, path: P, replication: u16) -> Result<()> {
+        self.for_tree(path.as_ref(), true, |path| self.set_replication(path, replication))
+    }
+
+    // Applies `apply` to every entry below `path` and then to `path` itself.
+    // Directories are listed before they are changed, so taking away access
+    // to them does not cut the walk short.
+    fn for_tree<F: Fn(&Path) -> Result<()>>(&self, path: &Path, files_only: bool, apply: F) -> Result<()> {
+        let is_dir = self.metadata(path)?.is_dir();
+        if is_dir {
+            for entry in self.walk_dir(path) {
+                let entry = entry?;
+                if !(files_only && entry.file_type().is_dir()) {
+                    apply(entry.path())?;
                 }
             }
-            hdfsFreeHosts(block_hosts);
-            Ok(hosts_strings)
+        }
+
+        match is_dir && files_only {
+            true => Ok(()),
+            false => apply(path),
         }
     }
 
-    
+    // The blocks of a file overlapping the byte `range`, each with the hosts
+    // serving it (`hdfsGetHosts`). Blocks are reported whole and are assumed
+    // to be `block_size` long except for the last one, which holds for files
+    // written in one go.
+    pub fn block_locations<P: AsRef<Path>, R: RangeBounds<u64>>(&self, path: P, range: R) -> Result<Vec<BlockLocation>> {
+        let file_path = check_path(path.as_ref())?;
+        let info = self.inner.backend.path_info(file_path)?;
+        if info.kind == ObjectKind::Directory {
+            return Err(HdfsError::new(HdfsErrorKind::IsADirectory,
+                format!("Directories have no blocks: {:?}", file_path)));
+        }
 
-    fn connect(&mut self) -> std::io::Result<()> {
-        let name_node_ptr = CString::new(self.name_node.as_bytes()).unwrap();
-        let fs = unsafe {
-            hdfsConnect(name_node_ptr.as_ptr(), 0)
+        let size = std::cmp::max(info.size, 0) as u64;
+        let block_size = match info.block_size {
+            block_size if block_size > 0 => block_size as u64,
+            _ => std::cmp::max(size, 1),
+        };
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
         };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end.saturating_add(1),
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => u64::MAX,
+        };
+        let end = std::cmp::min(end, size);
+        if start >= end {
+            return Ok(Vec::new());
+        }
 
-        match fs.is_null(){
-            false => {
-                self.fs = Some(fs);
-                Ok(())
+        let block_hosts = self.inner.backend.hosts(file_path, start as i64, (end - start) as i64)?;
+        let first_block = start / block_size;
+
+        let mut blocks = Vec::with_capacity(block_hosts.len());
+        for (i, hosts) in block_hosts.into_iter().enumerate() {
+            let offset = (first_block + i as u64) * block_size;
+            if offset >= size {
+                break;
             }
-            _ => {
-                Err(std::io::Error::new(std::io::ErrorKind::NotConnected, 
-                    format!("Failed to connect {}", self.name_node)))
+            blocks.push(BlockLocation { offset, length: std::cmp::min(block_size, size - offset), hosts });
+        }
+        Ok(blocks)
+    }
+
+    // Copies a file or a whole tree to `dst` on `dst_fs`, which may be this
+    // filesystem or a different one. `dst` must not exist.
+    pub fn copy_to<P: AsRef<Path>, Q: AsRef<Path>>(&self, src: P, dst_fs: &HdfsFileSystem, dst: Q) -> Result<()> {
+        self.transfer(src.as_ref(), dst_fs, dst.as_ref(), false)
+    }
+
+    // Like `copy_to`, but removes `src` afterwards. Within one filesystem
+    // this is a `rename`.
+    pub fn move_to<P: AsRef<Path>, Q: AsRef<Path>>(&self, src: P, dst_fs: &HdfsFileSystem, dst: Q) -> Result<()> {
+        match self.same_filesystem(dst_fs)? {
+            // `dst` is relative to the working directory of `dst_fs`
+            true => self.rename(src, dst_fs.absolute(dst)?),
+            false => self.transfer(src.as_ref(), dst_fs, dst.as_ref(), true),
+        }
+    }
+
+    // Whether `other` reaches the same files as this filesystem: a clone, or
+    // another libhdfs handle to the same namenode connected as the same user.
+    fn same_filesystem(&self, other: &HdfsFileSystem) -> Result<bool> {
+        if Arc::ptr_eq(&self.inner, &other.inner) {
+            return Ok(true);
+        }
+        if self.as_raw().is_none() || other.as_raw().is_none() || self.inner.user != other.inner.user {
+            return Ok(false);
+        }
+
+        // libhdfs qualifies the working directory with the resolved namenode
+        let (ours, theirs) = (self.working_directory()?, other.working_directory()?);
+        Ok(ours.scheme().map(str::to_ascii_lowercase) == theirs.scheme().map(str::to_ascii_lowercase)
+            && ours.authority().map(str::to_ascii_lowercase) == theirs.authority().map(str::to_ascii_lowercase))
+    }
+
+    fn transfer(&self, src: &Path, dst_fs: &HdfsFileSystem, dst: &Path, move_source: bool) -> Result<()> {
+        let (src_path, dst_path) = (check_path(src)?, check_path(dst)?);
+        let src_info = self.inner.backend.path_info(src_path)?;
+        if dst_fs.exists(dst)? {
+            return Err(HdfsError::new(HdfsErrorKind::AlreadyExists,
+                format!("{:?} already exists", dst_path)));
+        }
+        if self.same_filesystem(dst_fs)?
+            && Path::new(dst_fs.absolute(dst)?.path()).starts_with(self.absolute(src)?.path()) {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Cannot copy {:?} into itself", src_path)));
+        }
 
+        // two clusters (or two handles to one) can leave the work to Hadoop
+        if let (Some(src_fs), Some(raw_dst_fs)) = (self.as_raw(), dst_fs.as_raw()) {
+            return crate::backend::libhdfs::copy_between(src_fs, src_path, raw_dst_fs, dst_path, move_source);
+        }
+
+        self.copy_tree(src, src_info.kind == ObjectKind::Directory, dst_fs, dst)?;
+        if move_source {
+            self.inner.backend.delete(src_path, true)?;
+        }
+        Ok(())
+    }
+
+    fn copy_tree(&self, src: &Path, is_dir: bool, dst_fs: &HdfsFileSystem, dst: &Path) -> Result<()> {
+        if is_dir {
+            dst_fs.create_dir_all(dst)?;
+            for entry in self.read_dir(src)? {
+                let entry = entry?;
+                self.copy_tree(entry.path(), entry.file_type().is_dir(), dst_fs, &dst.join(entry.file_name()))?;
+            }
+            return Ok(());
+        }
+
+        let mut reader = self.open(src)?;
+        let mut writer = HdfsOpenOptions::new().write(true).create_new(true).open(dst_fs, dst)?;
+        std::io::copy(&mut reader, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn status(&self) -> Result<FsStatus> {
+        let capacity = std::cmp::max(self.inner.backend.capacity()?, 0) as u64;
+        let used = std::cmp::max(self.inner.backend.used()?, 0) as u64;
+        Ok(FsStatus { capacity, used, remaining: capacity.saturating_sub(used) })
+    }
+
+    // The block size new files get unless they ask for another one.
+    pub fn default_block_size(&self) -> Result<u64> {
+        Ok(std::cmp::max(self.inner.backend.default_block_size(None)?, 0) as u64)
+    }
+
+    // Like `default_block_size`, for files created under `path`, which may
+    // be on a filesystem mounted with other settings (e.g. with ViewFs).
+    pub fn default_block_size_at<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = check_path(path.as_ref())?;
+        Ok(std::cmp::max(self.inner.backend.default_block_size(Some(path))?, 0) as u64)
+    }
+
+    // Totals of the tree under `path` (a file counts as itself), gathered by
+    // listing every directory in it.
+    pub fn content_summary<P: AsRef<Path>>(&self, path: P) -> Result<ContentSummary> {
+        let path = path.as_ref();
+        let metadata = self.metadata(path)?;
+
+        let mut summary = ContentSummary::default();
+        summary.add(&metadata);
+        if metadata.is_dir() {
+            for entry in self.walk_dir(path) {
+                summary.add(entry?.metadata());
             }
         }
+        Ok(summary)
     }
 
-    fn open_with_flag(&mut self, flag: u32) -> std::io::Result<()> {
-        let file_path = self.path.to_string_lossy();
-        let file_path = CString::new(file_path.as_bytes()).unwrap();
-        let fs = self.fs.unwrap();
+    // Bytes in the files under `path`, like `hdfs dfs -du -s`.
+    pub fn du<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        Ok(self.content_summary(path)?.length)
+    }
+
+    // Hedged read counters of the whole process, see `HedgedReadMetrics`.
+    pub fn hedged_read_metrics(&self) -> Result<HedgedReadMetrics> {
+        self.inner.backend.hedged_read_metrics()
+    }
 
-        let file_exists = unsafe {
-            hdfsExists(fs, file_path.as_ptr()) == 0
+    // Lists a directory. Listing a file is an error, like for
+    // `std::fs::read_dir`, although `hdfsListDirectory` would return the file.
+    pub fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<ReadDir> {
+        let path = path.as_ref();
+        let dir_path = check_path(path)?;
+        let entries = self.inner.backend.list(dir_path)?;
+
+        // a file lists as itself: a single file entry at the listed path
+        // rather than below it
+        let is_file = match entries.as_slice() {
+            [entry] if entry.kind == ObjectKind::File =>
+                HdfsPath::new(&entry.name)?.path() == self.absolute(path)?.path(),
+            _ => false,
         };
+        if is_file {
+            return Err(HdfsError::new(HdfsErrorKind::NotADirectory,
+                format!("Not a directory: {:?}", path)));
+        }
+
+        Ok(ReadDir::new(self.clone(), path.to_path_buf(), entries))
+    }
+
+    // Recursively walks the tree below `root`, see `WalkDir`.
+    pub fn walk_dir<P: Into<PathBuf>>(&self, root: P) -> WalkDir {
+        WalkDir::new(self, root)
+    }
+
+    // The paths matching a glob pattern, see `Glob`.
+    pub fn glob(&self, pattern: &str) -> Result<Vec<DirEntry>> {
+        Glob::new(pattern).expand(self)
+    }
+}
+
+impl std::fmt::Debug for HdfsFileSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HdfsFileSystem")
+            .field("name_node", &self.inner.name_node)
+            .finish()
+    }
+}
+
+
+pub struct HdfsFile {
+    pub name_node: String, 
+    // absolute once the file is opened, so that changing the working
+    // directory does not affect open files; see `HdfsPath`
+    pub path: PathBuf,
+    pub read_pos: i64,
+    pub size: i64,
+    pub block_size: i64,
+    fs: HdfsFileSystem,
+    opened_file: Option<Box<dyn BackendFile>>, 
+    writing: bool,
+    // the `[start, end)` range a split reader is limited to
+    split: Option<(i64, i64)>,
+}
+
+
+impl HdfsFile {
+
+    // Attempts to open a file in read-only mode on the default namenode.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<HdfsFile> {
+        HdfsFileSystem::connect_default()?.open(path)
+    }
+
+    // Opens a file in write mode on the default namenode.
+    pub fn create<P: Into<PathBuf>>(path: P) -> Result<HdfsFile> {
+        HdfsFileSystem::connect_default()?.create(path)
+    }
+
+    // Attempts to open a file named by a URI in read-only mode, see
+    // `HdfsFileSystem::from_uri`.
+    pub fn open_uri<S: AsRef<str>>(uri: S) -> Result<HdfsFile> {
+        let uri = HdfsPath::new(uri)?;
+        FsUri::parse(&uri)?.connect()?.open(uri.path())
+    }
+
+    // Options for opening a file beyond `open` and `create`, e.g. for
+    // appending or for picking the replication of a new file.
+    pub fn with_options() -> HdfsOpenOptions {
+        HdfsOpenOptions::new()
+    }
+
+    // A reader over the `[start, end)` byte range of `path`; the file is only
+    // opened on the first read. Reads stop at `end` (or at the end of the
+    // file, if that comes first) and seeking before `start` fails.
+    pub fn from_split<P: Into<PathBuf>>(fs: &HdfsFileSystem, path: P, start: i64, end: i64) -> HdfsFile {
+
+        let mut reader = HdfsFile::unopened(fs.clone(), path.into());
+        reader.read_pos = start;
+        reader.size = end;
+        reader.split = Some((start, end));
+
+        reader
+    }
+
+    pub(crate) fn unopened(fs: HdfsFileSystem, path: PathBuf) -> HdfsFile {
+        HdfsFile {
+            name_node: fs.name_node().to_string(),
+            path,
+            read_pos: 0,
+            size: 0,
+            block_size: 0,
+            fs,
+            opened_file: None,
+            writing: false,
+            split: None,
+        }
+    }
+
+    pub fn file_system(&self) -> &HdfsFileSystem {
+        &self.fs
+    }
+
+    // Fresh metadata for `path`; unlike `size`, this reflects writes made
+    // since the file was opened.
+    pub fn metadata(&self) -> Result<Metadata> {
+        self.fs.metadata(&self.path)
+    }
+
+    // The blocks of this file overlapping `range`, see
+    // `HdfsFileSystem::block_locations`.
+    pub fn block_locations<R: RangeBounds<u64>>(&self, range: R) -> Result<Vec<BlockLocation>> {
+        self.fs.block_locations(&self.path, range)
+    }
+
+    // Where the bytes read so far came from; all zero until the first read.
+    pub fn read_statistics(&self) -> Result<ReadStatistics> {
+        self.check_reader()?;
+        match self.opened_file.as_ref() {
+            Some(file) => file.read_statistics(),
+            None => Ok(ReadStatistics::default()),
+        }
+    }
+
+    pub fn clear_read_statistics(&mut self) -> Result<()> {
+        self.check_reader()?;
+        match self.opened_file.as_mut() {
+            Some(file) => file.clear_read_statistics(),
+            None => Ok(()),
+        }
+    }
+
+    fn check_reader(&self) -> Result<()> {
+        match self.writing {
+            true => Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("{:?} is not open for reading", self.path))),
+            false => Ok(()),
+        }
+    }
+
+    fn open_with_flag(&mut self, flag: u32) -> Result<()> {
+        self.open_with_request(&OpenRequest::new(flag as i32))
+    }
+
+    pub(crate) fn open_with_request(&mut self, request: &OpenRequest) -> Result<()> {
+        self.path = self.fs.absolute(&self.path)?.into();
+        let backend = self.fs.backend();
+        let file_path = check_path(&self.path)?;
+
+        let file_exists = backend.exists(file_path)?;
+
+        let flag = request.flags as u32;
+        self.writing = (flag & O_ACCMODE) != O_RDONLY;
 
         let create_flag = (flag & O_CREAT) != 0;
         match (file_exists, create_flag) {
 
             (false, false) => {
-                Err(std::io::Error::new(std::io::ErrorKind::NotFound, 
+                Err(HdfsError::new(HdfsErrorKind::NotFound,
                     format!("No such file: {:?}", self.path)))
             }
 
             (true, _) => {
-                let file_info_ptr = unsafe {
-                    hdfsGetPathInfo(fs, file_path.as_ptr())
-                };
+                let file_info = backend.path_info(file_path)?;
+                let opened_file = backend.open(file_path, request)?;
 
-                match file_info_ptr.is_null() {
-                    true => {
-                        Err(std::io::Error::new(std::io::ErrorKind::Other, 
-                            format!("Failed to obtained info for {:?}", self.path)))
-                    }
-                    false => {
-
-                        let file_info = unsafe {
-                            *file_info_ptr
-                        };
-
-                        let file_size = file_info.mSize;
-                        let block_size = file_info.mBlockSize;
-                        let opened_file = unsafe {
-                            hdfsOpenFile(fs, file_path.as_ptr(), flag as i32, 0, 0, 0)
-                        };
-
-                        match opened_file.is_null(){
-                            true => {
-                                Err(std::io::Error::new(std::io::ErrorKind::Other, 
-                                    format!("Failed to open: {:?}", self.path)))
-                            }
-                            false => {
-                                self.size = file_size;
-                                self.block_size = block_size;
-                                self.opened_file = Some(opened_file);
-                                Ok(())
-                            }
-                        }
-                    }
-                }
+                self.size = match self.split {
+                    Some((_, end)) => std::cmp::min(file_info.size, end),
+                    None => file_info.size,
+                };
+                self.block_size = file_info.block_size;
+                self.opened_file = Some(opened_file);
+                Ok(())
             }
 
             _ => {
-
-                let opened_file = unsafe {
-                    hdfsOpenFile(fs, file_path.as_ptr(), flag as i32, 0, 0, 0)
-                };
-
-                match opened_file.is_null(){
-                    true => {
-                        Err(std::io::Error::new(std::io::ErrorKind::Other, 
-                            format!("Failed to create: {:?}", self.path)))
-                    }
-                    false => {
-                        self.opened_file = Some(opened_file);
-                        Ok(())
-                    }
-                }
+                let opened_file = backend.open(file_path, request)?;
+                self.opened_file = Some(opened_file);
+                Ok(())
             }
         }
     }
 
-    pub fn close(&mut self) {
-        match self.opened_file {
-            Some(file) => {
-                unsafe { hdfsCloseFile(self.fs.unwrap(), file); }
-                self.opened_file = None; 
-            }, 
-            _ => {},
+    // The backend stream of a reader, opened on first use like `read` does.
+    pub(crate) fn read_stream(&mut self) -> Result<&mut Box<dyn BackendFile>> {
+        if self.opened_file.is_none() {
+            self.open_with_flag(O_RDONLY)?;
         }
-
-        match self.fs {
-            Some(fs) => {
-                unsafe { hdfsDisconnect(fs); }
-                self.fs = None; 
-            }, 
-            _ => {},
+        if self.writing {
+            return Err(HdfsError::new(HdfsErrorKind::Unsupported,
+                format!("Cannot read {:?} while writing", self.path)));
         }
+        self.opened_file()
     }
-    
-    pub fn delete(&mut self) -> std::io::Result<()>{
-
-        match self.opened_file {
-            Some(file) => {
-                unsafe { hdfsCloseFile(self.fs.unwrap(), file); }
-                self.opened_file = None; 
-            }, 
-            _ => {},
-        };
 
-        let file_path = self.path.to_string_lossy();
-        let file_path = CString::new(file_path.as_bytes()).unwrap();
+    fn opened_file(&mut self) -> Result<&mut Box<dyn BackendFile>> {
+        let path = &self.path;
+        self.opened_file.as_mut().ok_or_else(|| HdfsError::new(HdfsErrorKind::NotConnected,
+            format!("File is not open: {:?}", path)))
+    }
 
-        let result = unsafe {
-            hdfsDelete(self.fs.unwrap(), file_path.as_ptr(), 0)
-        };
+    // Makes everything written so far visible to new readers (`hdfsHFlush`).
+    pub fn hflush(&mut self) -> Result<()> {
+        self.opened_file()?.hflush()
+    }
+
+    // Like `hflush`, and also waits until the datanodes have persisted the
+    // data to disk (`hdfsHSync`).
+    pub fn hsync(&mut self) -> Result<()> {
+        self.opened_file()?.hsync()
+    }
 
-        match result {
-            0 => Ok(()),
-            _ => Err(std::io::Error::new(std::io::ErrorKind::Other, 
-                format!("Failed to delete {:?}", self.path))),
+    pub fn close(&mut self) {
+        if let Some(mut file) = self.opened_file.take() {
+            let _ = file.close();
         }
     }
+    
+    pub fn delete(&mut self) -> Result<()>{
+        self.close();
+        self.fs.delete(&self.path)
+    }
 }
 
 impl Drop for HdfsFile {
     fn drop(&mut self) {
-        match self.opened_file {
-            Some(file) => {
-                unsafe { hdfsCloseFile(self.fs.unwrap(), file); }
-                self.opened_file = None; 
-            }, 
-            _ => {},
-        }
-
-        match self.fs {
-            Some(fs) => {
-                unsafe { hdfsDisconnect(fs); }
-                self.fs = None; 
-            }, 
-            _ => {},
-        }
+        self.close();
     }
 }
 
+// Largest request a single native read or write can take (`tSize` is an
+// `i32`).
+const MAX_IO_CHUNK: usize = i32::MAX as usize;
+
+// Reads are positioned at `read_pos` and never go past `size`. Like any
+// `Read`, a call may return fewer bytes than asked for; only buffers too big
+// for one native call are filled with several.
 impl Read for HdfsFile {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let remaining_size = self.size - self.read_pos;
-        let read_size = std::cmp::min((buf.len()) as i32, remaining_size as i32);
+        if self.opened_file.is_none() {
+            self.open_with_flag(O_RDONLY)?;
+        }
 
-        match self.opened_file {
-            Some(_) => {},
-            _ => {
-                self.connect().unwrap();
-                self.open_with_flag(O_RDONLY).unwrap();
+        let remaining_size = std::cmp::max(self.size - self.read_pos, 0) as u64;
+        let read_size = std::cmp::min(buf.len() as u64, remaining_size) as usize;
+
+        let read_pos = self.read_pos;
+        let file = self.opened_file()?;
+        let mut total_read = 0;
+        while total_read < read_size {
+            let chunk = std::cmp::min(read_size - total_read, MAX_IO_CHUNK);
+            let chunk_buf = &mut buf[total_read..total_read + chunk];
+
+            let read_bytes = match file.pread(read_pos + total_read as i64, chunk_buf) {
+                Ok(read_bytes) => std::cmp::min(read_bytes, chunk),
+                // hand out what we have; the error comes back on the next call
+                Err(_) if total_read > 0 => break,
+                Err(err) => return Err(err.into()),
+            };
+
+            total_read += read_bytes;
+            if read_bytes < chunk {
+                break;
             }
         }
 
-        unsafe {
-            hdfsPread(
-                self.fs.unwrap(), 
-                self.opened_file.unwrap(), 
-                self.read_pos, 
-            buf.as_mut_ptr() as *mut c_void, read_size);
+        self.read_pos += total_read as i64;
+        Ok(total_read)
+    }
+}
+
+// Readers can seek anywhere at or after offset 0 (the start of the range for
+// split readers), including past the end (where reads return 0 bytes);
+// `read_pos` is the authoritative position and the backend stream is kept in
+// step with it. HDFS files being written are append-only, so writers only
+// report their position.
+impl Seek for HdfsFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => return self.seek_to(offset as i128),
+            SeekFrom::End(offset) => (self.size, offset),
+            SeekFrom::Current(offset) if self.writing => (self.opened_file()?.tell()?, offset),
+            SeekFrom::Current(offset) => (self.read_pos, offset),
         };
-        
-        self.read_pos += read_size as i64;
+        self.seek_to(base as i128 + offset as i128)
+    }
+}
 
-        Ok(read_size as usize)
+impl HdfsFile {
+    fn seek_to(&mut self, position: i128) -> std::io::Result<u64> {
+        let lowest = self.split.map_or(0, |(start, _)| std::cmp::max(start, 0)) as i128;
+        if position < lowest || position > i64::MAX as i128 {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Invalid seek to {} in {:?}", position, self.path)).into());
+        }
+        let position = position as i64;
+
+        if self.writing {
+            let written = self.opened_file()?.tell()?;
+            return match position == written {
+                true => Ok(written as u64),
+                false => Err(HdfsError::new(HdfsErrorKind::Unsupported,
+                    format!("Cannot seek {:?} while writing", self.path)).into()),
+            };
+        }
+
+        // the native stream cannot be placed past EOF; reads there return 0
+        // without touching it
+        if position <= self.size {
+            if let Some(file) = self.opened_file.as_mut() {
+                file.seek(position)?;
+            }
+        }
+
+        self.read_pos = position;
+        Ok(position as u64)
     }
 }
 
+// `flush` only pushes client-side buffers (`hdfsFlush`); use `hflush` or
+// `hsync` when the data has to survive a client crash.
 impl Write for HdfsFile {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let buf_ptr = buf.as_ptr() as *const c_void;
-        let written_bytes = unsafe {
-            hdfsWrite(self.fs.unwrap(), self.opened_file.unwrap(), buf_ptr, buf.len() as i32)
-        };
+        let file = self.opened_file()?;
+        let mut total_written = 0;
+        while total_written < buf.len() {
+            let chunk = std::cmp::min(buf.len() - total_written, MAX_IO_CHUNK);
 
-        Ok(written_bytes as usize)
+            let written_bytes = match file.write(&buf[total_written..total_written + chunk]) {
+                Ok(written_bytes) => std::cmp::min(written_bytes, chunk),
+                // report what was written; the error comes back on the next call
+                Err(_) if total_written > 0 => break,
+                Err(err) => return Err(err.into()),
+            };
+
+            total_written += written_bytes;
+            if written_bytes < chunk {
+                break;
+            }
+        }
 
+        Ok(total_written)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
+        Ok(self.opened_file()?.flush()?)
+    }
+}
 
-        let result = unsafe {
-            hdfsFlush(self.fs.unwrap(), self.opened_file.unwrap())
-        };
+// Lists a directory on the default namenode.
+pub fn read_dir<P: AsRef<Path>>(path: P) -> Result<ReadDir> {
+    HdfsFileSystem::connect_default()?.read_dir(path)
+}
 
-        match result {
-            0 => Ok(()),
-            _ => Err(std::io::Error::new(std::io::ErrorKind::WriteZero, 
-                "failed to flush to hdfs")),
-        }
+// HDFS paths are Java strings, so they must be valid UTF-8 and cannot
+// contain NUL bytes.
+pub(crate) fn check_path(path: &Path) -> Result<&str> {
+    let path_str = path.to_str().ok_or_else(|| HdfsError::new(HdfsErrorKind::InvalidInput,
+        format!("Path is not valid UTF-8: {:?}", path)))?;
+    match path_str.contains('\0') {
+        false => Ok(path_str),
+        true => Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+            format!("Unexpected NUL byte in {:?}", path_str))),
     }
 }
 
-pub fn read_dir<P: AsRef<Path>>(path: P) -> Vec<HdfsFile>{
+pub(crate) fn to_cstring(value: &str) -> Result<CString> {
+    CString::new(value).map_err(|_| HdfsError::new(HdfsErrorKind::InvalidInput,
+        format!("Unexpected NUL byte in {:?}", value)))
+}
 
-    let mut file_list = Vec::new();
 
-    let name_node = CString::new("default").unwrap();
-    let fs = unsafe {
-        hdfsConnect(name_node.as_ptr(), 0)
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use rand::{thread_rng, Rng};
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::backend::MemoryBackend;
 
-    let file_path = path.as_ref().to_string_lossy();
-    let file_path = CString::new(file_path.as_bytes()).unwrap();
+    fn memory_fs() -> HdfsFileSystem {
+        HdfsFileSystem::with_backend("memory", MemoryBackend::new())
+    }
 
-    let mut num_entries: i32 = 0;
-    let list_result = unsafe {
-        hdfsListDirectory(fs, file_path.as_ptr(), &mut num_entries)
-    };
+    #[test]
+    fn check_path_rejects_nul_and_non_utf8() {
+        assert_eq!(check_path(Path::new("/user/etl/part-0000")).unwrap(),
+            "/user/etl/part-0000");
+        assert_eq!(check_path(Path::new("/a\0b")).unwrap_err().kind(), HdfsErrorKind::InvalidInput);
 
-    let list_result = unsafe {
-        std::slice::from_raw_parts(list_result, num_entries as usize)
-    };
+        let non_utf8 = Path::new(OsStr::from_bytes(b"/data/\xff\xfe"));
+        assert_eq!(check_path(non_utf8).unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+    }
 
-    for result in list_result {
-        let file_name = (*result).mName;
-        let file_name = unsafe {
-            CStr::from_ptr(file_name).to_str().unwrap()
-        }; 
+    #[test]
+    fn check_path_never_panics() {
+        let mut rng = thread_rng();
+        for _ in 0..10_000 {
+            let len = rng.gen_range(0, 32);
+            let bytes: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let path = Path::new(OsStr::from_bytes(&bytes));
 
-        let file_size = (*result).mSize;
-        let file_block_size = (*result).mBlockSize;
+            let valid = !bytes.contains(&0) && std::str::from_utf8(&bytes).is_ok();
+            assert_eq!(check_path(path).is_ok(), valid, "{:?}", bytes);
+        }
+    }
 
-        let hdfs_file = HdfsFile {
-            name_node: String::from("default"),
-            path: PathBuf::from(file_name),
-            read_pos:0,
-            size: file_size,
-            block_size: file_block_size,
-            fs: None,
-            opened_file: None
-        };
+    #[test]
+    fn write_then_read_back() {
+        let fs = memory_fs();
+
+        let mut writer = fs.create("/logs/app.log").unwrap();
+        writer.write_all(b"first line\nsecond line\n").unwrap();
+        writer.flush().unwrap();
+        writer.close();
+
+        let mut content = String::new();
+        fs.open("/logs/app.log").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "first line\nsecond line\n");
+    }
+
+    #[test]
+    fn read_dir_lists_children() {
+        let fs = memory_fs();
+        fs.create("/dir/a").unwrap().write_all(b"aa").unwrap();
+        fs.create("/dir/b/c").unwrap();
+
+        let entries: Vec<DirEntry> = fs.read_dir("/dir").unwrap().map(|entry| entry.unwrap()).collect();
+        let names: Vec<_> = entries.iter().map(|entry| entry.file_name()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(entries[0].path(), Path::new("/dir/a"));
+        assert_eq!(entries[0].metadata().len(), 2);
+        assert!(entries[1].file_type().is_dir());
+
+        // paths are relative to the listed directory, not URIs
+        let entry = fs.read_dir("dir/").unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.path(), Path::new("dir/a"));
+
+        let mut content = String::new();
+        entry.open().unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "aa");
+    }
+
+    #[test]
+    fn read_dir_of_empty_dirs_and_files() {
+        let fs = memory_fs();
+        fs.create("/dir/f").unwrap();
+        fs.delete("/dir/f").unwrap();
+
+        assert_eq!(fs.read_dir("/dir").unwrap().count(), 0);
+        fs.create("/dir/f").unwrap();
+        assert_eq!(fs.read_dir("/dir/f").unwrap_err().kind(), HdfsErrorKind::NotADirectory);
+
+        // lists just like the file `/dir/f` itself would
+        fs.create("/dir/g/g").unwrap();
+        let paths: Vec<_> = fs.read_dir("/dir/g/").unwrap().map(|entry| entry.unwrap().path().to_path_buf()).collect();
+        assert_eq!(paths, vec![PathBuf::from("/dir/g/g")]);
+        fs.set_working_directory("/dir").unwrap();
+        assert_eq!(fs.read_dir("g").unwrap().count(), 1);
+        assert_eq!(fs.read_dir("f").unwrap_err().kind(), HdfsErrorKind::NotADirectory);
+    }
+
+    #[test]
+    fn missing_files_are_errors() {
+        let fs = memory_fs();
+
+        assert_eq!(fs.open("/missing").err().unwrap().kind(), HdfsErrorKind::NotFound);
+        assert_eq!(fs.read_dir("/missing").err().unwrap().kind(), HdfsErrorKind::NotFound);
+        assert_eq!(fs.delete("/missing").unwrap_err().kind(), HdfsErrorKind::NotFound);
+        assert!(!fs.exists("/missing").unwrap());
+    }
+
+    #[test]
+    fn closed_file_is_an_error() {
+        let fs = memory_fs();
+        let mut writer = fs.create("/f").unwrap();
+        writer.close();
 
-        file_list.push(hdfs_file);
+        assert_eq!(writer.write(b"x").unwrap_err().kind(), std::io::ErrorKind::NotConnected);
+        assert_eq!(writer.flush().unwrap_err().kind(), std::io::ErrorKind::NotConnected);
     }
 
-    unsafe { hdfsDisconnect(fs) };
-    file_list
-}
\ No newline at end of file
+    #[test]
+    fn fuzzed_paths_never_panic() {
+        let fs = memory_fs();
+        let mut rng = thread_rng();
+        let alphabet = b"/ab.\0\xff\xc3";
+
+        for _ in 0..2_000 {
+            let len = rng.gen_range(0, 12);
+            let bytes: Vec<u8> = (0..len).map(|_| alphabet[rng.gen_range(0, alphabet.len())]).collect();
+            let path = PathBuf::from(OsStr::from_bytes(&bytes));
+
+            let _ = fs.exists(&path);
+            let _ = fs.read_dir(&path);
+            if let Ok(mut file) = fs.create(&path) {
+                let _ = file.write_all(&bytes);
+            }
+            if let Ok(mut file) = fs.open(&path) {
+                let _ = file.read_to_end(&mut Vec::new());
+            }
+            let _ = HdfsFile::from_split(&fs, &path, 3, 1).read_to_end(&mut Vec::new());
+            let _ = fs.delete(&path);
+        }
+    }
+
+    #[test]
+    fn create_remove_and_rename() {
+        let fs = memory_fs();
+        fs.create_dir_all("/a/b/c").unwrap();
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create("/a/b/c/f").unwrap().write_all(b"data").unwrap();
+
+        assert_eq!(fs.remove_file("/a/b").unwrap_err().kind(), HdfsErrorKind::IsADirectory);
+        assert_eq!(fs.remove_dir_all("/a/b/c/f").unwrap_err().kind(), HdfsErrorKind::NotADirectory);
+
+        fs.create_dir_all("/d").unwrap();
+        assert_eq!(fs.rename("/a/b", "/d").unwrap_err().kind(), HdfsErrorKind::AlreadyExists);
+        fs.rename("/a/b", "/d/b").unwrap();
+        assert!(fs.metadata("/d/b/c/f").unwrap().is_file());
+
+        fs.remove_file("/d/b/c/f").unwrap();
+        assert!(fs.metadata("/d/b/c").unwrap().is_dir());
+        fs.remove_dir_all("/d").unwrap();
+        assert!(!fs.exists("/d/b").unwrap());
+        assert!(fs.exists("/a").unwrap());
+    }
+
+    #[test]
+    fn copy_and_move_between_filesystems() {
+        let (src, dst) = (memory_fs(), memory_fs());
+        src.create("/tree/a").unwrap().write_all(b"aaa").unwrap();
+        src.create("/tree/sub/b").unwrap().write_all(b"bbb").unwrap();
+        src.create_dir_all("/tree/empty").unwrap();
+
+        src.copy_to("/tree", &dst, "/copy").unwrap();
+        let mut content = String::new();
+        dst.open("/copy/sub/b").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "bbb");
+        assert!(dst.metadata("/copy/empty").unwrap().is_dir());
+        assert!(src.exists("/tree/a").unwrap());
+
+        assert_eq!(src.copy_to("/tree", &dst, "/copy").unwrap_err().kind(), HdfsErrorKind::AlreadyExists);
+        assert_eq!(src.copy_to("/tree", &src, "/tree/sub/x").unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+
+        src.move_to("/tree/a", &dst, "/moved").unwrap();
+        assert!(!src.exists("/tree/a").unwrap());
+        assert_eq!(dst.metadata("/moved").unwrap().len(), 3);
+
+        // within one filesystem, moving is a rename
+        src.move_to("/tree", &src.clone(), "/renamed").unwrap();
+        assert!(src.exists("/renamed/sub/b").unwrap());
+        assert!(src.same_filesystem(&src.clone()).unwrap());
+        assert!(!src.same_filesystem(&dst).unwrap());
+    }
+
+    #[test]
+    #[ignore = "requires a live HDFS cluster"]
+    fn handles_to_one_namenode_are_the_same_filesystem() {
+        let connect = || HdfsFileSystem::builder().force_new_instance(true).connect().unwrap();
+        let (fs, other) = (connect(), connect());
+        assert!(fs.same_filesystem(&other).unwrap());
+
+        let as_other_user = HdfsFileSystem::builder().user("nobody").force_new_instance(true).connect().unwrap();
+        assert!(!fs.same_filesystem(&as_other_user).unwrap());
+    }
+
+    #[test]
+    fn permissions_owner_and_times() {
+        let fs = memory_fs();
+        fs.create("/data/f").unwrap();
+
+        fs.set_permissions("/data/f", "rw-r-----".parse().unwrap()).unwrap();
+        fs.set_owner("/data/f", Some("etl"), None).unwrap();
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        fs.set_times("/data/f", Some(modified), None).unwrap();
+
+        let metadata = fs.metadata("/data/f").unwrap();
+        assert_eq!(metadata.permissions().mode(), 0o640);
+        assert_eq!((metadata.owner(), metadata.group()), ("etl", "supergroup"));
+        assert_eq!(metadata.modified(), modified);
+        assert!(metadata.accessed() > modified);
+
+        let before_epoch = UNIX_EPOCH - Duration::from_secs(1);
+        assert_eq!(fs.set_times("/data/f", Some(before_epoch), None).unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+        assert_eq!(fs.set_owner("/missing", Some("etl"), None).unwrap_err().kind(), HdfsErrorKind::NotFound);
+    }
+
+    #[test]
+    fn recursive_attribute_changes() {
+        let fs = memory_fs();
+        fs.create("/t/a").unwrap();
+        fs.create("/t/p/b").unwrap();
+
+        fs.set_permissions_recursive("/t", Permissions::from_mode(0o1770)).unwrap();
+        fs.set_owner_recursive("/t", None, Some("analysts")).unwrap();
+        fs.set_replication_recursive("/t", 5).unwrap();
+        for path in &["/t", "/t/a", "/t/p", "/t/p/b"] {
+            let metadata = fs.metadata(path).unwrap();
+            assert_eq!(metadata.permissions().to_string(), "rwxrwx--T");
+            assert_eq!(metadata.group(), "analysts");
+        }
+        assert_eq!(fs.metadata("/t/p/b").unwrap().replication(), 5);
+        assert_eq!(fs.metadata("/t/p").unwrap().replication(), 0);
+
+        assert_eq!(fs.set_replication("/t/p", 2).unwrap_err().kind(), HdfsErrorKind::IsADirectory);
+        assert_eq!(fs.set_replication("/t/a", 0).unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+        fs.set_replication_recursive("/t/a", 2).unwrap();
+        assert_eq!(fs.metadata("/t/a").unwrap().replication(), 2);
+    }
+
+    #[test]
+    fn block_locations_cover_the_range() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new().with_block_size(4));
+        fs.create("/f").unwrap().write_all(b"0123456789").unwrap();
+
+        let blocks = fs.block_locations("/f", ..).unwrap();
+        let extents: Vec<_> = blocks.iter().map(|block| (block.offset, block.length)).collect();
+        assert_eq!(extents, vec![(0, 4), (4, 4), (8, 2)]);
+        assert_eq!(blocks[0].hosts, vec!["localhost"]);
+
+        let blocks = fs.block_locations("/f", 5..=8).unwrap();
+        assert_eq!(blocks.iter().map(|block| block.offset).collect::<Vec<_>>(), vec![4, 8]);
+        assert_eq!(blocks[1].end(), 10);
+
+        assert!(fs.block_locations("/f", 10..).unwrap().is_empty());
+        assert!(fs.block_locations("/f", 3..3).unwrap().is_empty());
+        assert_eq!(fs.open("/f").unwrap().block_locations(..4).unwrap().len(), 1);
+        assert_eq!(fs.block_locations("/", ..).unwrap_err().kind(), HdfsErrorKind::IsADirectory);
+
+        // a zero block size falls back to the default instead of dividing by it
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new().with_block_size(0));
+        fs.create("/f").unwrap().write_all(b"0123456789").unwrap();
+        assert_eq!(fs.block_locations("/f", ..).unwrap().iter().map(|block| (block.offset, block.length)).collect::<Vec<_>>(),
+            vec![(0, 10)]);
+    }
+
+    #[test]
+    fn capacity_and_content_summary() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new()
+            .with_capacity(1000).with_replication(2).with_block_size(64));
+        fs.create("/data/a").unwrap().write_all(&[0; 100]).unwrap();
+        fs.create("/data/sub/b").unwrap().write_all(&[0; 50]).unwrap();
+        fs.create_dir_all("/data/empty").unwrap();
+        fs.create("/other").unwrap().write_all(&[0; 200]).unwrap();
+
+        assert_eq!(fs.status().unwrap(), FsStatus { capacity: 1000, used: 700, remaining: 300 });
+        assert_eq!(fs.default_block_size().unwrap(), 64);
+        assert_eq!(fs.default_block_size_at("/data").unwrap(), 64);
+
+        let summary = fs.content_summary("/data").unwrap();
+        assert_eq!(summary, ContentSummary { length: 150, file_count: 2, directory_count: 3, space_consumed: 300 });
+        assert_eq!(fs.content_summary("/other").unwrap().file_count, 1);
+        assert_eq!(fs.du("/").unwrap(), 350);
+        assert_eq!(fs.du("/missing").unwrap_err().kind(), HdfsErrorKind::NotFound);
+    }
+
+    #[test]
+    fn read_statistics_and_hedged_reads() {
+        let fs = memory_fs();
+        let mut writer = fs.create("/f").unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        assert_eq!(writer.read_statistics().unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+        drop(writer);
+
+        let mut reader = HdfsFile::from_split(&fs, "/f", 2, 8);
+        assert_eq!(reader.read_statistics().unwrap(), ReadStatistics::default());
+
+        reader.read_exact(&mut [0; 4]).unwrap();
+        let statistics = reader.read_statistics().unwrap();
+        assert_eq!((statistics.total_bytes, statistics.local_bytes, statistics.remote_bytes), (4, 4, 0));
+        assert_eq!(statistics.locality(), 1.0);
+
+        reader.clear_read_statistics().unwrap();
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(reader.read_statistics().unwrap().total_bytes, 2);
+
+        let remote = ReadStatistics { total_bytes: 4, local_bytes: 1, remote_bytes: 3, ..ReadStatistics::default() };
+        assert_eq!(remote.locality(), 0.25);
+
+        assert_eq!(fs.hedged_read_metrics().unwrap_err().kind(), HdfsErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn relative_paths_use_the_working_directory() {
+        let fs = memory_fs();
+        assert_eq!(fs.working_directory().unwrap().as_str(), "/");
+
+        fs.create_dir_all("/user/etl").unwrap();
+        fs.set_working_directory("/user/etl").unwrap();
+        fs.create("data/part-0").unwrap().write_all(b"abc").unwrap();
+        assert!(fs.exists("/user/etl/data/part-0").unwrap());
+        assert_eq!(fs.absolute("data/../logs").unwrap().as_str(), "/user/etl/logs");
+        assert_eq!(fs.absolute("/tmp").unwrap().as_str(), "/tmp");
+        assert_eq!(fs.absolute("data:2024/part-0").unwrap().as_str(), "/user/etl/data:2024/part-0");
+        assert_eq!(fs.absolute("hdfs://nn/tmp").unwrap().as_str(), "hdfs://nn/tmp");
+
+        let names: Vec<String> = fs.read_dir("data").unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string())
+            .collect();
+        assert_eq!(names, vec!["part-0"]);
+        assert_eq!(fs.glob("data/part-*").unwrap().len(), 1);
+
+        fs.rename("data/part-0", "part-0").unwrap();
+        let mut reader = fs.open("part-0").unwrap();
+        assert_eq!(reader.path, PathBuf::from("/user/etl/part-0"));
+
+        // open files keep their absolute path
+        fs.set_working_directory("..").unwrap();
+        assert_eq!(fs.working_directory().unwrap().as_str(), "/user");
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "abc");
+        assert!(!fs.exists("part-0").unwrap());
+        assert!(fs.exists("etl/part-0").unwrap());
+    }
+
+    #[test]
+    fn seek_within_and_past_eof() {
+        let fs = memory_fs();
+        fs.create("/f").unwrap().write_all(b"0123456789").unwrap();
+        let mut file = fs.open("/f").unwrap();
+        let mut buf = [0u8; 3];
+
+        assert_eq!(file.seek(SeekFrom::Start(4)).unwrap(), 4);
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"456");
+
+        assert_eq!(file.seek(SeekFrom::Current(-5)).unwrap(), 2);
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"234");
+
+        assert_eq!(file.seek(SeekFrom::End(-2)).unwrap(), 8);
+        assert_eq!(file.read(&mut buf).unwrap(), 2);
+        assert_eq!(file.stream_position().unwrap(), 10);
+
+        assert_eq!(file.seek(SeekFrom::End(5)).unwrap(), 15);
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+        assert_eq!(file.seek(SeekFrom::Start(1)).unwrap(), 1);
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"123");
+    }
+
+    #[test]
+    fn seek_to_negative_offset_fails() {
+        let fs = memory_fs();
+        fs.create("/f").unwrap().write_all(b"0123456789").unwrap();
+        let mut file = fs.open("/f").unwrap();
+        file.seek(SeekFrom::Start(3)).unwrap();
+
+        assert_eq!(file.seek(SeekFrom::Current(-4)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(file.seek(SeekFrom::End(-11)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(file.seek(SeekFrom::Current(i64::MIN)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        // a failed seek leaves the position alone
+        assert_eq!(file.stream_position().unwrap(), 3);
+    }
+
+    #[test]
+    fn seek_on_writer_only_reports_position() {
+        let fs = memory_fs();
+        let mut writer = fs.create("/f").unwrap();
+        writer.write_all(b"abc").unwrap();
+
+        assert_eq!(writer.stream_position().unwrap(), 3);
+        assert_eq!(writer.seek(SeekFrom::Start(3)).unwrap(), 3);
+        assert_eq!(writer.seek(SeekFrom::Start(0)).unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn short_reads_report_real_byte_count() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new().with_max_read_size(3));
+        let content: Vec<u8> = (0..100).collect();
+        fs.create("/f").unwrap().write_all(&content).unwrap();
+
+        let mut file = fs.open("/f").unwrap();
+        let mut buf = [0u8; 10];
+        assert_eq!(file.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], &[0, 1, 2]);
+        assert_eq!(file.read_pos, 3);
+
+        // nothing is skipped or duplicated across short reads
+        let mut rest = Vec::new();
+        file.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &content[3..]);
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn reads_stop_at_eof() {
+        let fs = memory_fs();
+        fs.create("/f").unwrap().write_all(b"0123456789").unwrap();
+
+        let mut file = fs.open("/f").unwrap();
+        file.seek(SeekFrom::Start(7)).unwrap();
+        let mut buf = [0u8; 10];
+        assert_eq!(file.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"789");
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+        assert_eq!(file.read_pos, 10);
+    }
+
+    #[test]
+    fn read_errors_surface() {
+        let fs = memory_fs();
+        fs.create("/f").unwrap().write_all(b"0123456789").unwrap();
+
+        let mut file = fs.open("/f").unwrap();
+        fs.delete("/f").unwrap();
+
+        let mut buf = [0u8; 4];
+        assert_eq!(file.read(&mut buf).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(file.read_pos, 0);
+    }
+
+    #[test]
+    fn short_writes_report_real_byte_count() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new().with_max_write_size(4));
+        let mut writer = fs.create("/f").unwrap();
+
+        assert_eq!(writer.write(b"0123456789").unwrap(), 4);
+        writer.write_all(b"456789").unwrap();
+        writer.close();
+
+        let mut content = String::new();
+        fs.open("/f").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "0123456789");
+    }
+
+    #[test]
+    fn write_errors_surface() {
+        let fs = memory_fs();
+        fs.create("/f").unwrap().write_all(b"abc").unwrap();
+
+        let mut reader = fs.open("/f").unwrap();
+        assert!(reader.write(b"x").is_err());
+        assert!(reader.hflush().is_err());
+
+        let mut writer = fs.create("/g").unwrap();
+        fs.delete("/g").unwrap();
+        assert_eq!(writer.write(b"x").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn hflush_and_hsync() {
+        let fs = memory_fs();
+        let mut writer = fs.create("/wal").unwrap();
+        writer.write_all(b"entry-1\n").unwrap();
+        writer.hflush().unwrap();
+        writer.write_all(b"entry-2\n").unwrap();
+        writer.hsync().unwrap();
+
+        let mut content = String::new();
+        fs.open("/wal").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "entry-1\nentry-2\n");
+
+        writer.close();
+        assert_eq!(writer.hsync().unwrap_err().kind(), HdfsErrorKind::NotConnected);
+    }
+}
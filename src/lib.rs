@@ -1,6 +1,7 @@
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
+#![allow(clippy::redundant_static_lifetimes)]
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
@@ -9,6 +10,34 @@ extern crate rand;
 
 pub mod hdfs_fs;
 pub use hdfs_fs::*;
+pub mod backend;
+pub mod builder;
+pub use builder::*;
+pub mod cache;
+pub use cache::FileSystemCache;
+pub mod open_options;
+pub use open_options::*;
+pub mod metadata;
+pub use metadata::*;
+pub mod dir;
+pub use dir::{DirEntry, ReadDir};
+pub mod walk;
+pub use walk::{WalkDir, WalkDirIter};
+pub mod glob;
+pub use glob::{glob, Glob};
+pub mod split;
+pub use split::{InputSplit, SplitPlanner};
+pub mod lines;
+pub use lines::LineSplitReader;
+pub mod zero_copy;
+pub use zero_copy::{ZeroCopyBuffer, ZeroCopyReader};
+pub mod path;
+pub use path::HdfsPath;
+mod uri;
+pub mod statistics;
+pub use statistics::{HedgedReadMetrics, ReadStatistics};
+pub mod error;
+pub use error::{HdfsError, HdfsErrorKind};
 
 #[cfg(test)]
 mod tests {
@@ -24,6 +53,7 @@ mod tests {
 
 
 	#[test]
+	#[ignore = "requires a live HDFS cluster"]
 	fn hdfs_raw_write(){
 		unsafe{
 			let name_node = CString::new("default").unwrap();
@@ -51,6 +81,7 @@ mod tests {
 
 
 	#[test] 
+	#[ignore = "requires a live HDFS cluster"]
 	fn test_hdfs_fs_write() {
 		let random_str = get_ramdon_string();
 		let path = ["/", random_str.as_str()].concat();
@@ -68,13 +99,14 @@ mod tests {
 	}
 
 	#[test] 
+	#[ignore = "requires a live HDFS cluster"]
 	fn test_hdfs_fs_read(){
 		let random_str = get_ramdon_string();
 		let path = ["/", random_str.as_str()].concat();
 		let mut hdfs_writer = HdfsFile::create(path.as_str()).unwrap();	
 
 		let buffer = String::from("HHHHHello\nworldddddd\n");
-		hdfs_writer.write(buffer.as_bytes()).unwrap();
+		hdfs_writer.write_all(buffer.as_bytes()).unwrap();
 		hdfs_writer.flush().unwrap();
 		hdfs_writer.close();
 
@@ -87,12 +119,13 @@ mod tests {
 	}
 
 	#[test] 
+	#[ignore = "requires a live HDFS cluster"]
 	fn test_hdfs_fs_read_dir() {
 		let path = String::from("/");
-		let entries = read_dir(path);
+		let entries = read_dir(path).unwrap();
 
 		for entry in entries {
-			println!("{}",entry.path.to_string_lossy());
+			println!("{}",entry.unwrap().path().to_string_lossy());
 			// let reader = BufReader::new(entry);
 			// for line in reader.lines() {
 			// 	println!("{}", line.unwrap());
@@ -100,6 +133,62 @@ mod tests {
 		}
 	}
 
+	#[test]
+	#[ignore = "requires a live HDFS cluster"]
+	fn test_hdfs_fs_shared_handle() {
+		let fs = HdfsFileSystem::connect("default").unwrap();
+		let path = ["/", get_ramdon_string().as_str()].concat();
+
+		let mut writer = fs.create(path.as_str()).unwrap();
+		writer.write_all(b"shared").unwrap();
+		writer.close();
+
+		// dropping one clone must not disconnect the others
+		let other = fs.clone();
+		drop(fs);
+
+		assert!(other.exists(path.as_str()).unwrap());
+		let mut content = String::new();
+		other.open(path.as_str()).unwrap().read_to_string(&mut content).unwrap();
+		assert_eq!(content, "shared");
+
+		other.delete(path.as_str()).unwrap();
+	}
+
+	#[test]
+	#[ignore = "requires a live HDFS cluster"]
+	fn test_hdfs_fs_open_missing() {
+		let fs = HdfsFileSystem::connect_default().unwrap();
+		let path = ["/", get_ramdon_string().as_str(), "/missing"].concat();
+
+		assert_eq!(fs.open(path.as_str()).err().unwrap().kind(), HdfsErrorKind::NotFound);
+		assert!(!fs.exists(path.as_str()).unwrap());
+		assert!(fs.read_dir(path.as_str()).is_err());
+		assert_eq!(fs.open("/bad\0path").err().unwrap().kind(), HdfsErrorKind::InvalidInput);
+	}
+
+	#[test]
+	#[ignore = "requires a live HDFS cluster"]
+	fn test_hdfs_fs_builder() {
+		let fs = HdfsFileSystem::builder()
+			.force_new_instance(true)
+			.conf("dfs.client.use.datanode.hostname", "false")
+			.connect()
+			.unwrap();
+
+		assert!(fs.exists("/").unwrap());
+	}
+
+	#[test]
+	fn test_hdfs_fs_builder_rejects_nul() {
+		let err = HdfsFileSystem::builder()
+			.conf("dfs.replication", "3\0")
+			.connect()
+			.unwrap_err();
+
+		assert_eq!(err.kind(), HdfsErrorKind::InvalidInput);
+	}
+
 	fn get_ramdon_string() -> String {
 		thread_rng().sample_iter(&Alphanumeric).take(10).collect()
 	}
@@ -0,0 +1,320 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::*;
+use crate::error::Result;
+
+
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+
+// Reads the lines of one split of a text file, with the semantics of
+// Hadoop's `LineRecordReader`: a line belongs to the split its first byte is
+// in, so every line of the file is read by exactly one of a set of adjacent
+// splits.
+//
+//     let mut lines = LineSplitReader::new(&fs, "/logs/app.log", start, end);
+//     while let Some(offset) = lines.read_record(&mut line)? {
+//         ...
+//     }
+//
+// A split not starting at offset 0 skips the partial line it starts in, and
+// the last line is read to its end even when that is past the end of the
+// split. Lines are returned without their delimiter; by default that is any
+// of `\n`, `\r\n` and `\r`. A custom delimiter that can overlap itself (such
+// as `||`) is matched from where the split starts reading, so runs of it
+// straddling the start of a split can be misread, as in Hadoop.
+pub struct LineSplitReader {
+    input: Input,
+    start: u64,
+    end: u64,
+    delimiter: Option<Vec<u8>>,
+    started: bool,
+    done: bool,
+}
+
+impl LineSplitReader {
+
+    // A reader over the lines starting in `(start, end]` (or `[0, end]` for
+    // the first split); the file is only opened on the first read.
+    pub fn new<P: Into<PathBuf>>(fs: &HdfsFileSystem, path: P, start: u64, end: u64) -> LineSplitReader {
+        LineSplitReader {
+            input: Input {
+                file: HdfsFile::unopened(fs.clone(), path.into()),
+                buffer: Vec::new(),
+                buffer_pos: 0,
+                buffer_len: 0,
+                pos: start,
+            },
+            start,
+            end,
+            delimiter: None,
+            started: false,
+            done: false,
+        }
+    }
+
+    pub fn delimiter<D: Into<Vec<u8>>>(mut self, delimiter: D) -> Self {
+        self.delimiter = Some(delimiter.into());
+        self
+    }
+
+    // Bytes fetched from the file per read; 64 KiB by default.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.input.buffer = vec![0; std::cmp::max(buffer_size, 1)];
+        self
+    }
+
+    // Offset of the next byte to be read.
+    pub fn position(&self) -> u64 {
+        self.input.pos
+    }
+
+    // Reads the next line of the split into `record`, replacing its contents,
+    // and returns its offset in the file; `None` once the split is done.
+    pub fn read_record(&mut self, record: &mut Vec<u8>) -> Result<Option<u64>> {
+        record.clear();
+        if !self.started {
+            if self.delimiter.as_ref().is_some_and(Vec::is_empty) {
+                return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                    format!("Empty line delimiter for {:?}", self.input.file.path)));
+            }
+            self.skip_partial_line()?;
+            self.started = true;
+        }
+
+        // the line starting right at `end` still belongs to this split
+        if self.input.pos > self.end || !self.input.fill()? {
+            return Ok(None);
+        }
+
+        let offset = self.input.pos;
+        self.input.read_line(self.delimiter.as_deref(), record)?;
+        Ok(Some(offset))
+    }
+
+    // Moves to the first line starting after `start`; a line starting right
+    // at `start` belongs to the previous split.
+    fn skip_partial_line(&mut self) -> Result<()> {
+        if self.input.buffer.is_empty() {
+            self.input.buffer = vec![0; DEFAULT_BUFFER_SIZE];
+        }
+        if self.start == 0 {
+            return Ok(());
+        }
+
+        // a delimiter ending after `start` begins at most its length minus
+        // one bytes before it
+        let lookbehind = self.delimiter.as_ref().map_or(0, |delimiter| delimiter.len() as u64 - 1);
+        self.input.pos = self.start.saturating_sub(lookbehind);
+        self.input.file.seek(SeekFrom::Start(self.input.pos))?;
+        self.input.read_line(self.delimiter.as_deref(), &mut Vec::new())
+    }
+}
+
+// The file being read, buffered.
+struct Input {
+    file: HdfsFile,
+    buffer: Vec<u8>,
+    buffer_pos: usize,
+    buffer_len: usize,
+    // offset in the file of the next byte to consume
+    pos: u64,
+}
+
+impl Input {
+
+    // Whether there is anything left to read, refilling the buffer if needed.
+    fn fill(&mut self) -> Result<bool> {
+        if self.buffer_pos < self.buffer_len {
+            return Ok(true);
+        }
+        self.buffer_len = self.file.read(&mut self.buffer)?;
+        self.buffer_pos = 0;
+        Ok(self.buffer_len > 0)
+    }
+
+    fn consume(&mut self, count: usize) {
+        self.buffer_pos += count;
+        self.pos += count as u64;
+    }
+
+    // Appends the bytes up to the next delimiter (or the end of the file) to
+    // `line` and consumes them along with the delimiter.
+    fn read_line(&mut self, delimiter: Option<&[u8]>, line: &mut Vec<u8>) -> Result<()> {
+        match delimiter {
+            Some(delimiter) => self.read_until(delimiter, line),
+            None => self.read_until_newline(line),
+        }
+    }
+
+    // `\n`, `\r\n` or `\r`.
+    fn read_until_newline(&mut self, line: &mut Vec<u8>) -> Result<()> {
+        while self.fill()? {
+            let available = &self.buffer[self.buffer_pos..self.buffer_len];
+            match available.iter().position(|&b| b == b'\n' || b == b'\r') {
+                Some(i) => {
+                    let terminator = available[i];
+                    line.extend_from_slice(&available[..i]);
+                    self.consume(i + 1);
+                    if terminator == b'\r' && self.fill()? && self.buffer[self.buffer_pos] == b'\n' {
+                        self.consume(1);
+                    }
+                    return Ok(());
+                }
+                None => {
+                    line.extend_from_slice(available);
+                    let taken = available.len();
+                    self.consume(taken);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_until(&mut self, delimiter: &[u8], line: &mut Vec<u8>) -> Result<()> {
+        let last = delimiter[delimiter.len() - 1];
+        while self.fill()? {
+            let available = &self.buffer[self.buffer_pos..self.buffer_len];
+
+            // the delimiter may have started in an earlier buffer, so it is
+            // matched against the end of `line` too
+            let found = (0..available.len())
+                .find(|&i| available[i] == last && ends_with(line, &available[..=i], delimiter));
+
+            let taken = found.map_or(available.len(), |i| i + 1);
+            line.extend_from_slice(&available[..taken]);
+            self.consume(taken);
+            if found.is_some() {
+                line.truncate(line.len() - delimiter.len());
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+// Whether `head` followed by `tail` ends with `suffix`.
+fn ends_with(head: &[u8], tail: &[u8], suffix: &[u8]) -> bool {
+    if tail.len() >= suffix.len() {
+        return tail.ends_with(suffix);
+    }
+    let (from_head, from_tail) = suffix.split_at(suffix.len() - tail.len());
+    from_tail == tail && head.ends_with(from_head)
+}
+
+// Yields `(offset, line)` pairs; iteration stops after the first error.
+impl Iterator for LineSplitReader {
+    type Item = Result<(u64, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Result<(u64, Vec<u8>)>> {
+        if self.done {
+            return None;
+        }
+
+        let mut line = Vec::new();
+        match self.read_record(&mut line) {
+            Ok(Some(offset)) => Some(Ok((offset, line))),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for LineSplitReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LineSplitReader")
+            .field("path", &self.input.file.path)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .field("delimiter", &self.delimiter)
+            .field("pos", &self.input.pos)
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::fs_with;
+
+    // Every line read by splitting the file into `split_size` byte splits.
+    fn read_split(fs: &HdfsFileSystem, len: u64, split_size: u64, delimiter: Option<&[u8]>, buffer_size: usize) -> Vec<(u64, Vec<u8>)> {
+        let mut lines = Vec::new();
+        for start in (0..len).step_by(split_size as usize) {
+            let mut reader = LineSplitReader::new(fs, "/f", start, std::cmp::min(start + split_size, len))
+                .buffer_size(buffer_size);
+            if let Some(delimiter) = delimiter {
+                reader = reader.delimiter(delimiter);
+            }
+            lines.extend(reader.map(Result::unwrap));
+        }
+        lines
+    }
+
+    fn check_every_split_size(content: &[u8], delimiter: Option<&[u8]>, expected: &[(u64, &str)]) {
+        let fs = fs_with(content);
+        let expected: Vec<_> = expected.iter().map(|(offset, line)| (*offset, line.as_bytes().to_vec())).collect();
+        let len = content.len() as u64;
+
+        for split_size in 1..=len + 1 {
+            for buffer_size in &[1, 2, 3, 64] {
+                assert_eq!(read_split(&fs, len, split_size, delimiter, *buffer_size), expected,
+                    "split size {}, buffer size {}", split_size, buffer_size);
+            }
+        }
+    }
+
+    #[test]
+    fn default_delimiters() {
+        check_every_split_size(b"a\nbb\r\nccc\rdddd\n\n\r\neee", None,
+            &[(0, "a"), (2, "bb"), (6, "ccc"), (10, "dddd"), (15, ""), (16, ""), (18, "eee")]);
+        check_every_split_size(b"\none\n", None, &[(0, ""), (1, "one")]);
+        check_every_split_size(b"\r\r\n", None, &[(0, ""), (1, "")]);
+    }
+
+    #[test]
+    fn custom_delimiters() {
+        check_every_split_size(b"x<|>yy<|><|>z<|>", Some(b"<|>"), &[(0, "x"), (4, "yy"), (9, ""), (12, "z")]);
+        check_every_split_size(b"<|z<|>\n<", Some(b"<|>"), &[(0, "<|z"), (6, "\n<")]);
+        check_every_split_size(b"a\nb;c", Some(b";"), &[(0, "a\nb"), (4, "c")]);
+    }
+
+    #[test]
+    fn reads_past_the_end_of_the_split() {
+        let fs = fs_with(b"first line\nsecond line\nthird");
+
+        let mut reader = LineSplitReader::new(&fs, "/f", 0, 3);
+        let mut line = Vec::new();
+        assert_eq!(reader.read_record(&mut line).unwrap(), Some(0));
+        assert_eq!(line, b"first line");
+        assert_eq!(reader.position(), 11);
+        assert_eq!(reader.read_record(&mut line).unwrap(), None);
+        assert!(line.is_empty());
+
+        let lines: Vec<_> = LineSplitReader::new(&fs, "/f", 3, 11).map(Result::unwrap).collect();
+        assert_eq!(lines, vec![(11, b"second line".to_vec())]);
+        assert_eq!(LineSplitReader::new(&fs, "/f", 11, 20).count(), 0);
+        assert_eq!(LineSplitReader::new(&fs, "/f", 11, 23).count(), 1);
+        assert_eq!(LineSplitReader::new(&fs, "/f", 29, 40).count(), 0);
+    }
+
+    #[test]
+    fn errors_end_iteration() {
+        let fs = fs_with(b"a\nb");
+
+        let mut reader = LineSplitReader::new(&fs, "/missing", 0, 10);
+        assert_eq!(reader.next().unwrap().unwrap_err().kind(), HdfsErrorKind::NotFound);
+        assert!(reader.next().is_none());
+
+        let mut reader = LineSplitReader::new(&fs, "/f", 0, 10).delimiter("");
+        assert_eq!(reader.next().unwrap().unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+    }
+}
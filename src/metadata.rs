@@ -0,0 +1,370 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{HdfsError, HdfsErrorKind};
+use crate::backend::{FileInfo, ObjectKind};
+use crate::error::Result;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileType {
+    File,
+    Directory,
+}
+
+impl FileType {
+
+    pub fn is_file(self) -> bool {
+        self == FileType::File
+    }
+
+    pub fn is_dir(self) -> bool {
+        self == FileType::Directory
+    }
+}
+
+impl From<ObjectKind> for FileType {
+    fn from(kind: ObjectKind) -> FileType {
+        match kind {
+            ObjectKind::File => FileType::File,
+            ObjectKind::Directory => FileType::Directory,
+        }
+    }
+}
+
+
+// The permission bits of a file or directory: read, write and execute for
+// the owner, the group and others, plus the sticky bit. HDFS has no setuid
+// or setgid bits.
+//
+// Displays like `ls -l` (`rwxr-x---`, with `t`/`T` for the sticky bit) and
+// parses from that form or from an octal mode such as `750` or `1777`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Permissions {
+    mode: u16,
+}
+
+const STICKY_BIT: u16 = 0o1000;
+
+impl Permissions {
+
+    pub fn from_mode(mode: u16) -> Permissions {
+        Permissions { mode: mode & 0o1777 }
+    }
+
+    pub fn mode(&self) -> u16 {
+        self.mode
+    }
+
+    // Whether only owners may delete or rename the entries of a directory.
+    pub fn sticky(&self) -> bool {
+        self.mode & STICKY_BIT != 0
+    }
+
+    pub fn with_sticky(self, sticky: bool) -> Permissions {
+        match sticky {
+            true => Permissions { mode: self.mode | STICKY_BIT },
+            false => Permissions { mode: self.mode & !STICKY_BIT },
+        }
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut symbolic = String::with_capacity(9);
+        for (bit, c) in (0..9).rev().zip("rwxrwxrwx".chars()) {
+            symbolic.push(if self.mode & (1 << bit) != 0 { c } else { '-' });
+        }
+        if self.sticky() {
+            let others_execute = self.mode & 0o001 != 0;
+            symbolic.pop();
+            symbolic.push(if others_execute { 't' } else { 'T' });
+        }
+        f.pad(&symbolic)
+    }
+}
+
+impl fmt::Octal for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Octal::fmt(&self.mode, f)
+    }
+}
+
+impl FromStr for Permissions {
+    type Err = HdfsError;
+
+    fn from_str(value: &str) -> Result<Permissions> {
+        let invalid = || HdfsError::new(HdfsErrorKind::InvalidInput,
+            format!("Invalid permissions {:?}", value));
+
+        if !value.is_empty() && value.len() <= 4 && value.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+            let mode = u16::from_str_radix(value, 8).map_err(|_| invalid())?;
+            return match mode <= 0o1777 {
+                true => Ok(Permissions::from_mode(mode)),
+                false => Err(invalid()),
+            };
+        }
+
+        // `ls -l` style, optionally with the file type in front
+        let chars: Vec<char> = value.chars().collect();
+        let chars = match chars.len() {
+            10 if chars[0] == '-' || chars[0] == 'd' => &chars[1..],
+            9 => &chars[..],
+            _ => return Err(invalid()),
+        };
+
+        let mut mode = 0;
+        for (i, (c, expected)) in chars.iter().zip("rwxrwxrwx".chars()).enumerate() {
+            let bit = 1 << (8 - i);
+            match *c {
+                '-' => {},
+                c if c == expected => mode |= bit,
+                't' if i == 8 => mode |= bit | STICKY_BIT,
+                'T' if i == 8 => mode |= STICKY_BIT,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(Permissions::from_mode(mode))
+    }
+}
+
+
+// Everything `hdfsGetPathInfo` knows about a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    file_type: FileType,
+    name: String,
+    len: u64,
+    modified: SystemTime,
+    accessed: SystemTime,
+    replication: u16,
+    block_size: u64,
+    owner: String,
+    group: String,
+    permissions: Permissions,
+}
+
+impl Metadata {
+
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type.is_file()
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type.is_dir()
+    }
+
+    // The fully qualified name, e.g. `hdfs://nn:8020/user/etl/part-0000`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Size in bytes; 0 for directories.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn modified(&self) -> SystemTime {
+        self.modified
+    }
+
+    pub fn accessed(&self) -> SystemTime {
+        self.accessed
+    }
+
+    // 0 for directories.
+    pub fn replication(&self) -> u16 {
+        self.replication
+    }
+
+    // 0 for directories.
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    pub fn permissions(&self) -> Permissions {
+        self.permissions
+    }
+}
+
+impl From<FileInfo> for Metadata {
+    fn from(info: FileInfo) -> Metadata {
+        Metadata {
+            file_type: info.kind.into(),
+            name: info.name,
+            len: std::cmp::max(info.size, 0) as u64,
+            modified: from_epoch_seconds(info.last_mod),
+            accessed: from_epoch_seconds(info.last_access),
+            replication: std::cmp::max(info.replication, 0) as u16,
+            block_size: std::cmp::max(info.block_size, 0) as u64,
+            owner: info.owner,
+            group: info.group,
+            permissions: Permissions::from_mode(info.permissions as u16),
+        }
+    }
+}
+
+// One block of a file and the datanodes holding a replica of it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlockLocation {
+    pub offset: u64,
+    pub length: u64,
+    pub hosts: Vec<String>,
+}
+
+impl BlockLocation {
+
+    pub fn end(&self) -> u64 {
+        self.offset + self.length
+    }
+}
+
+// Raw space of the whole filesystem in bytes, counting every replica.
+// libhdfs only reports capacity and usage, so `remaining` is `capacity -
+// used`; unlike the namenode's figure it still includes space taken by other
+// data on the datanodes' disks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FsStatus {
+    pub capacity: u64,
+    pub used: u64,
+    pub remaining: u64,
+}
+
+// Totals of a directory tree, like `hdfs dfs -count`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ContentSummary {
+    // bytes in files
+    pub length: u64,
+    pub file_count: u64,
+    // including the directory summarized
+    pub directory_count: u64,
+    // bytes in files times their replication
+    pub space_consumed: u64,
+}
+
+impl ContentSummary {
+
+    pub(crate) fn add(&mut self, metadata: &Metadata) {
+        match metadata.file_type() {
+            FileType::Directory => self.directory_count += 1,
+            FileType::File => {
+                self.file_count += 1;
+                self.length += metadata.len();
+                self.space_consumed += metadata.len() * metadata.replication() as u64;
+            }
+        }
+    }
+}
+
+fn from_epoch_seconds(seconds: i64) -> SystemTime {
+    match seconds >= 0 {
+        true => UNIX_EPOCH + Duration::from_secs(seconds as u64),
+        false => UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()),
+    }
+}
+
+// HDFS keeps times as whole seconds since the epoch; `-1` means "unset" to
+// libhdfs, so earlier times are rejected.
+pub(crate) fn to_epoch_seconds(time: SystemTime) -> Result<i64> {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) if duration.as_secs() <= i64::MAX as u64 => Ok(duration.as_secs() as i64),
+        _ => Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+            format!("Time out of range: {:?}", time))),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use crate::HdfsFileSystem;
+    use crate::backend::MemoryBackend;
+
+    #[test]
+    fn converts_file_info() {
+        let metadata = Metadata::from(FileInfo {
+            kind: ObjectKind::File,
+            name: String::from("hdfs://nn:8020/data/f"),
+            last_mod: 1_600_000_000,
+            size: 42,
+            replication: 3,
+            block_size: 128 * 1024 * 1024,
+            owner: String::from("etl"),
+            group: String::from("hadoop"),
+            permissions: 0o644,
+            last_access: -1,
+        });
+
+        assert!(metadata.is_file());
+        assert_eq!(metadata.name(), "hdfs://nn:8020/data/f");
+        assert_eq!(metadata.len(), 42);
+        assert_eq!(metadata.modified(), UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        assert_eq!(metadata.accessed(), UNIX_EPOCH - Duration::from_secs(1));
+        assert_eq!(metadata.replication(), 3);
+        assert_eq!(metadata.block_size(), 128 * 1024 * 1024);
+        assert_eq!((metadata.owner(), metadata.group()), ("etl", "hadoop"));
+        assert_eq!(metadata.permissions().mode(), 0o644);
+    }
+
+    #[test]
+    fn permissions_display_and_parse() {
+        let permissions = Permissions::from_mode(0o750);
+        assert_eq!(permissions.to_string(), "rwxr-x---");
+        assert_eq!(format!("{:o}", permissions), "750");
+        assert_eq!(Permissions::from_mode(0o1777).to_string(), "rwxrwxrwt");
+        assert_eq!(Permissions::from_mode(0o1770).to_string(), "rwxrwx--T");
+        assert_eq!(Permissions::from_mode(0o4755), Permissions::from_mode(0o755));
+
+        for mode in 0..=0o1777 {
+            let permissions = Permissions::from_mode(mode);
+            assert_eq!(permissions.to_string().parse::<Permissions>().unwrap(), permissions);
+            assert_eq!(format!("{:o}", permissions).parse::<Permissions>().unwrap(), permissions);
+        }
+
+        assert_eq!("drwxr-xr-x".parse::<Permissions>().unwrap().mode(), 0o755);
+        assert_eq!("0644".parse::<Permissions>().unwrap().mode(), 0o644);
+        assert!("1777".parse::<Permissions>().unwrap().sticky());
+        assert!(!Permissions::from_mode(0o1777).with_sticky(false).sticky());
+        for invalid in &["", "8", "2777", "rwxr-x--", "rwxr-x--x-", "xwrr-x---", "rwtr-x---"] {
+            assert_eq!(invalid.parse::<Permissions>().unwrap_err().kind(), HdfsErrorKind::InvalidInput, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn metadata_of_files_and_directories() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new().with_replication(2));
+        fs.create("/d/f").unwrap().write_all(b"abc").unwrap();
+
+        let file = fs.metadata("/d/f").unwrap();
+        assert_eq!(file.file_type(), FileType::File);
+        assert_eq!(file.len(), 3);
+        assert_eq!(file.replication(), 2);
+        assert_eq!(file.owner(), "hdfs");
+        assert!(file.modified() > UNIX_EPOCH);
+        assert_eq!(fs.open("/d/f").unwrap().metadata().unwrap(), file);
+
+        let dir = fs.metadata("/d").unwrap();
+        assert!(dir.is_dir());
+        assert_eq!(dir.permissions().mode(), 0o755);
+
+        assert_eq!(fs.metadata("/missing").unwrap_err().kind(), crate::HdfsErrorKind::NotFound);
+    }
+}
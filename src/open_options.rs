@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+
+use crate::*;
+use crate::backend::OpenRequest;
+use crate::error::Result;
+use crate::hdfs_fs::check_path;
+
+
+// Options for opening an `HdfsFile`, following `std::fs::OpenOptions`.
+//
+//     let mut log = HdfsFile::with_options()
+//         .append(true)
+//         .create(true)
+//         .open(&fs, "/logs/app.log")?;
+//
+// HDFS files are either read or written, never both, and existing files can
+// only be appended to or replaced as a whole, so `write` on an existing file
+// needs `truncate` or `append`. Replication and block size only apply to
+// files this creates; zero keeps the configured defaults.
+#[derive(Clone, Debug, Default)]
+pub struct HdfsOpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    create: bool,
+    create_new: bool,
+    truncate: bool,
+    buffer_size: i32,
+    replication: i16,
+    block_size: i64,
+}
+
+impl HdfsOpenOptions {
+
+    pub fn new() -> HdfsOpenOptions {
+        HdfsOpenOptions::default()
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    // Appends to the end of an existing file; implies `write`.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    // Creates the file if it is missing.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    // Creates the file, failing if it already exists.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    // Replaces the content of an existing file.
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    // Size of the client-side stream buffer.
+    pub fn buffer_size(&mut self, buffer_size: i32) -> &mut Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    pub fn replication(&mut self, replication: i16) -> &mut Self {
+        self.replication = replication;
+        self
+    }
+
+    pub fn block_size(&mut self, block_size: i64) -> &mut Self {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn open<P: Into<PathBuf>>(&self, fs: &HdfsFileSystem, path: P) -> Result<HdfsFile> {
+        let path = path.into();
+        if self.buffer_size < 0 || self.replication < 0 || self.block_size < 0 {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Negative buffer size, replication or block size for {:?}", path)));
+        }
+
+        let file_exists = fs.exists(&path)?;
+        let flags = self.flags(&path, file_exists)?;
+
+        let mut request = OpenRequest::new(flags as i32);
+        request.buffer_size = self.buffer_size;
+        if flags & O_CREAT != 0 {
+            request.replication = self.replication;
+            request.block_size = self.block_size;
+        }
+
+        let mut file = HdfsFile::unopened(fs.clone(), path);
+        file.open_with_request(&request)?;
+        Ok(file)
+    }
+
+    // The `hdfsOpenFile` flags for these options, given whether the file
+    // exists.
+    fn flags(&self, path: &Path, file_exists: bool) -> Result<u32> {
+        let path = check_path(path)?;
+        let writing = self.write || self.append;
+        let invalid = |message: &str| Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+            format!("{} for {:?}", message, path)));
+
+        if !self.read && !writing {
+            return invalid("Neither read, write nor append requested");
+        }
+        if self.read && writing {
+            return Err(HdfsError::new(HdfsErrorKind::Unsupported,
+                format!("HDFS files cannot be read and written at once: {:?}", path)));
+        }
+        if self.read {
+            return match self.create || self.create_new || self.truncate {
+                true => invalid("Create or truncate requested without write access"),
+                false => Ok(O_RDONLY),
+            };
+        }
+        if self.append && self.truncate {
+            return invalid("Both append and truncate requested");
+        }
+
+        if self.create_new {
+            return match file_exists {
+                true => Err(HdfsError::new(HdfsErrorKind::AlreadyExists,
+                    format!("File already exists: {:?}", path))),
+                false => Ok(O_WRONLY | O_CREAT),
+            };
+        }
+
+        match (file_exists, self.append) {
+            (true, true) => Ok(O_WRONLY | O_APPEND),
+            (true, false) if self.truncate => Ok(O_WRONLY | O_CREAT),
+            (true, false) => Err(HdfsError::new(HdfsErrorKind::Unsupported,
+                format!("HDFS cannot write into an existing file, use append or truncate: {:?}", path))),
+            (false, _) if self.create => Ok(O_WRONLY | O_CREAT),
+            (false, _) => Err(HdfsError::new(HdfsErrorKind::NotFound,
+                format!("No such file: {:?}", path))),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use crate::backend::MemoryBackend;
+
+    fn read_all(fs: &HdfsFileSystem, path: &str) -> String {
+        let mut content = String::new();
+        fs.open(path).unwrap().read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn append_and_create() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new());
+        let mut options = HdfsFile::with_options();
+        options.append(true);
+
+        assert_eq!(options.open(&fs, "/log").err().unwrap().kind(), HdfsErrorKind::NotFound);
+
+        options.create(true);
+        options.open(&fs, "/log").unwrap().write_all(b"one\n").unwrap();
+        options.open(&fs, "/log").unwrap().write_all(b"two\n").unwrap();
+        assert_eq!(read_all(&fs, "/log"), "one\ntwo\n");
+    }
+
+    #[test]
+    fn create_new_truncate_and_write() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new());
+        fs.create("/f").unwrap().write_all(b"old").unwrap();
+
+        let err = HdfsOpenOptions::new().write(true).create_new(true).open(&fs, "/f").err().unwrap();
+        assert_eq!(err.kind(), HdfsErrorKind::AlreadyExists);
+        let err = HdfsOpenOptions::new().write(true).create(true).open(&fs, "/f").err().unwrap();
+        assert_eq!(err.kind(), HdfsErrorKind::Unsupported);
+        assert_eq!(read_all(&fs, "/f"), "old");
+
+        HdfsOpenOptions::new().write(true).truncate(true).open(&fs, "/f").unwrap()
+            .write_all(b"new").unwrap();
+        assert_eq!(read_all(&fs, "/f"), "new");
+
+        HdfsOpenOptions::new().write(true).create_new(true).open(&fs, "/g").unwrap();
+        assert!(fs.exists("/g").unwrap());
+    }
+
+    #[test]
+    fn invalid_combinations() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new());
+        fs.create("/f").unwrap();
+
+        let kind = |options: &HdfsOpenOptions| options.open(&fs, "/f").err().unwrap().kind();
+        assert_eq!(kind(&HdfsOpenOptions::new()), HdfsErrorKind::InvalidInput);
+        assert_eq!(kind(HdfsOpenOptions::new().read(true).write(true)), HdfsErrorKind::Unsupported);
+        assert_eq!(kind(HdfsOpenOptions::new().read(true).truncate(true)), HdfsErrorKind::InvalidInput);
+        assert_eq!(kind(HdfsOpenOptions::new().append(true).truncate(true)), HdfsErrorKind::InvalidInput);
+        assert_eq!(kind(HdfsOpenOptions::new().read(true).buffer_size(-1)), HdfsErrorKind::InvalidInput);
+        assert!(HdfsOpenOptions::new().read(true).open(&fs, "/f").is_ok());
+    }
+
+    #[test]
+    fn replication_and_block_size_apply_to_new_files() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new());
+        let block_size = 4 * 1024 * 1024 * 1024;
+        HdfsOpenOptions::new().write(true).create(true).replication(2).block_size(block_size)
+            .open(&fs, "/big").unwrap();
+
+        let info = fs.backend().path_info("/big").unwrap();
+        assert_eq!(info.replication, 2);
+        assert_eq!(info.block_size, block_size);
+
+        let file = HdfsOpenOptions::new().read(true).open(&fs, "/big").unwrap();
+        assert_eq!(file.block_size, block_size);
+    }
+}
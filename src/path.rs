@@ -0,0 +1,290 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::{HdfsError, HdfsErrorKind};
+use crate::error::Result;
+
+
+// A Hadoop path: an optional scheme and authority followed by a
+// `/`-separated path, e.g. `hdfs://nn:8020/user/etl` or `data/part-0`.
+//
+// Paths are normalized when created: repeated and trailing slashes and `.`
+// components are dropped, and `..` removes the component before it (at the
+// root of an absolute path it is dropped). Unlike `std::path::Path` the
+// separator is always `/`, and the scheme and authority are kept apart from
+// the path, so `parent` and `join` never touch them.
+//
+// A prefix such as `data:` is only a scheme when followed by `//` or when
+// it names a Hadoop filesystem (`hdfs`, `viewfs`, `webhdfs`, `file`), so
+// `data:2024/part-0` is a relative path.
+//
+// `HdfsPath` converts into `PathBuf` and can be passed to every method
+// taking a path. Relative paths are resolved against the working directory
+// of the filesystem they are used with.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HdfsPath {
+    // the whole path, e.g. `hdfs://nn:8020/user/etl`
+    repr: String,
+    // where the path after the scheme and authority starts in `repr`
+    path_start: usize,
+}
+
+impl HdfsPath {
+
+    pub fn new<S: AsRef<str>>(path: S) -> Result<HdfsPath> {
+        let path = path.as_ref();
+        if path.is_empty() || path.contains('\0') {
+            return Err(HdfsError::new(HdfsErrorKind::InvalidInput,
+                format!("Invalid path {:?}", path)));
+        }
+
+        let (scheme, authority, path) = split_uri(path);
+        let mut prefix = String::new();
+        if let Some(scheme) = scheme {
+            prefix.push_str(scheme);
+            prefix.push(':');
+        }
+        if let Some(authority) = authority {
+            prefix.push_str("//");
+            prefix.push_str(authority);
+        }
+
+        // a path after an authority is always absolute
+        let path = match authority.is_some() && !path.starts_with('/') {
+            true => ["/", path].concat(),
+            false => path.to_string(),
+        };
+        Ok(HdfsPath::with_prefix(prefix, &path))
+    }
+
+    fn with_prefix(prefix: String, path: &str) -> HdfsPath {
+        let path_start = prefix.len();
+        let mut repr = prefix;
+        repr.push_str(&normalize(path));
+        HdfsPath { repr, path_start }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.repr
+    }
+
+    pub fn scheme(&self) -> Option<&str> {
+        split_uri(&self.repr).0
+    }
+
+    pub fn authority(&self) -> Option<&str> {
+        split_uri(&self.repr).1
+    }
+
+    // The path without scheme and authority, e.g. `/user/etl`.
+    pub fn path(&self) -> &str {
+        &self.repr[self.path_start..]
+    }
+
+    pub fn is_absolute(&self) -> bool {
+        self.path().starts_with('/')
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.path() == "/"
+    }
+
+    // The last component; `None` for the root, `.` and paths ending in `..`.
+    pub fn file_name(&self) -> Option<&str> {
+        match self.path().rsplit('/').next() {
+            Some("") | Some(".") | Some("..") | None => None,
+            file_name => file_name,
+        }
+    }
+
+    // The path without its last component, keeping scheme and authority;
+    // `None` whenever `file_name` is.
+    pub fn parent(&self) -> Option<HdfsPath> {
+        self.file_name()?;
+
+        let path = self.path();
+        let parent = match path.rfind('/') {
+            Some(0) => "/",
+            Some(slash) => &path[..slash],
+            None => ".",
+        };
+        Some(HdfsPath::with_prefix(self.repr[..self.path_start].to_string(), parent))
+    }
+
+    // `child` resolved against this path: a child with a scheme or authority
+    // replaces it, an absolute one keeps only its scheme and authority, and
+    // a relative one is appended.
+    pub fn join<S: AsRef<str>>(&self, child: S) -> Result<HdfsPath> {
+        let child = HdfsPath::new(child)?;
+        if child.path_start > 0 {
+            return Ok(child);
+        }
+
+        let prefix = self.repr[..self.path_start].to_string();
+        match child.is_absolute() {
+            true => Ok(HdfsPath::with_prefix(prefix, child.path())),
+            false => Ok(HdfsPath::with_prefix(prefix, &[self.path(), "/", child.path()].concat())),
+        }
+    }
+}
+
+// `scheme:` (a letter followed by letters, digits, `+`, `-` or `.`) and
+// `//authority`, each only when present.
+fn split_uri(path: &str) -> (Option<&str>, Option<&str>, &str) {
+    let (scheme, rest) = match path.find(':') {
+        Some(colon) if is_scheme(&path[..colon])
+            && (path[colon + 1..].starts_with("//") || is_known_scheme(&path[..colon])) =>
+            (Some(&path[..colon]), &path[colon + 1..]),
+        _ => (None, path),
+    };
+
+    match rest.strip_prefix("//") {
+        Some(rest) => {
+            let end = rest.find('/').unwrap_or(rest.len());
+            (scheme, Some(&rest[..end]), &rest[end..])
+        }
+        None => (scheme, None, rest),
+    }
+}
+
+fn is_scheme(scheme: &str) -> bool {
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+}
+
+fn is_known_scheme(scheme: &str) -> bool {
+    ["hdfs", "viewfs", "webhdfs", "file"].iter().any(|known| scheme.eq_ignore_ascii_case(known))
+}
+
+fn normalize(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => match components.last() {
+                Some(&last) if last != ".." => { components.pop(); },
+                _ if absolute => {},
+                _ => components.push(".."),
+            },
+            _ => components.push(component),
+        }
+    }
+
+    match (absolute, components.is_empty()) {
+        (true, _) => ["/", components.join("/").as_str()].concat(),
+        (false, true) => String::from("."),
+        (false, false) => components.join("/"),
+    }
+}
+
+impl FromStr for HdfsPath {
+    type Err = HdfsError;
+
+    fn from_str(path: &str) -> Result<HdfsPath> {
+        HdfsPath::new(path)
+    }
+}
+
+impl fmt::Display for HdfsPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.repr)
+    }
+}
+
+impl fmt::Debug for HdfsPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.repr, f)
+    }
+}
+
+impl AsRef<str> for HdfsPath {
+    fn as_ref(&self) -> &str {
+        &self.repr
+    }
+}
+
+impl AsRef<Path> for HdfsPath {
+    fn as_ref(&self) -> &Path {
+        Path::new(&self.repr)
+    }
+}
+
+impl From<HdfsPath> for PathBuf {
+    fn from(path: HdfsPath) -> PathBuf {
+        PathBuf::from(path.repr)
+    }
+}
+
+impl From<&HdfsPath> for PathBuf {
+    fn from(path: &HdfsPath) -> PathBuf {
+        PathBuf::from(&path.repr)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> HdfsPath {
+        HdfsPath::new(path).unwrap()
+    }
+
+    #[test]
+    fn parses_and_normalizes() {
+        let qualified = path("hdfs://nn:8020//user/./etl/../hive/");
+        assert_eq!(qualified.as_str(), "hdfs://nn:8020/user/hive");
+        assert_eq!((qualified.scheme(), qualified.authority(), qualified.path()), (Some("hdfs"), Some("nn:8020"), "/user/hive"));
+        assert!(qualified.is_absolute());
+
+        assert_eq!(path("hdfs://nn").as_str(), "hdfs://nn/");
+        assert_eq!(path("file:/tmp/x").authority(), None);
+        assert_eq!(path("/a/../../b").as_str(), "/b");
+        assert_eq!(path("a/./b/..").as_str(), "a");
+        assert_eq!(path("../a/../..").as_str(), "../..");
+        assert_eq!(path("a/..").as_str(), ".");
+        assert_eq!(path("2026-10-18:12/x").scheme(), None);
+        assert_eq!(path("data:2024/part-0").scheme(), None);
+        assert_eq!(path("s3a://bucket/x").scheme(), Some("s3a"));
+        assert_eq!(path("HDFS:/x").scheme(), Some("HDFS"));
+        assert!(!path("data/part-0").is_absolute());
+
+        for invalid in &["", "/a\0b"] {
+            assert_eq!(HdfsPath::new(invalid).unwrap_err().kind(), HdfsErrorKind::InvalidInput);
+        }
+        assert_eq!("/a/b".parse::<HdfsPath>().unwrap().to_string(), "/a/b");
+    }
+
+    #[test]
+    fn parents_and_file_names() {
+        let file = path("hdfs://nn/user/etl/part-0");
+        assert_eq!(file.file_name(), Some("part-0"));
+        assert_eq!(file.parent().unwrap().as_str(), "hdfs://nn/user/etl");
+        assert_eq!(path("/user").parent().unwrap().as_str(), "/");
+        assert_eq!(path("part-0").parent().unwrap().as_str(), ".");
+
+        for no_parent in &["/", "hdfs://nn", ".", "../.."] {
+            assert_eq!(path(no_parent).file_name(), None, "{}", no_parent);
+            assert_eq!(path(no_parent).parent(), None, "{}", no_parent);
+        }
+    }
+
+    #[test]
+    fn joins() {
+        let dir = path("hdfs://nn/user/etl");
+        assert_eq!(dir.join("data/../logs/").unwrap().as_str(), "hdfs://nn/user/etl/logs");
+        assert_eq!(dir.join("../hive").unwrap().as_str(), "hdfs://nn/user/hive");
+        assert_eq!(dir.join("/tmp").unwrap().as_str(), "hdfs://nn/tmp");
+        assert_eq!(dir.join("hdfs://other/tmp").unwrap().as_str(), "hdfs://other/tmp");
+        assert_eq!(dir.join("data:2024").unwrap().as_str(), "hdfs://nn/user/etl/data:2024");
+        assert_eq!(path(".").join("a").unwrap().as_str(), "a");
+        assert_eq!(path("a").join("../..").unwrap().as_str(), "..");
+        assert!(dir.join("").is_err());
+
+        let as_path: PathBuf = dir.clone().into();
+        assert_eq!(as_path, PathBuf::from("hdfs://nn/user/etl"));
+        assert_eq!(AsRef::<Path>::as_ref(&dir), Path::new("hdfs://nn/user/etl"));
+    }
+}
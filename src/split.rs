@@ -0,0 +1,343 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::*;
+use crate::error::Result;
+
+
+// Like Hadoop's `FileInputFormat`, the last split of a file may be up to 10%
+// larger than the target instead of leaving a tiny split behind.
+const SPLIT_SLOP: f64 = 1.1;
+
+
+// A byte range of one file, to be processed by one task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputSplit {
+    pub path: PathBuf,
+    pub start: u64,
+    pub length: u64,
+    // the hosts holding the most bytes of the split come first
+    pub hosts: Vec<String>,
+}
+
+impl InputSplit {
+
+    pub fn end(&self) -> u64 {
+        self.start + self.length
+    }
+
+    // A reader limited to `[start, end)`; the file is opened on first read.
+    pub fn open(&self, fs: &HdfsFileSystem) -> HdfsFile {
+        HdfsFile::from_split(fs, self.path.clone(), self.start as i64, self.end() as i64)
+    }
+
+    // The lines of a text file starting in this split, see `LineSplitReader`.
+    pub fn lines(&self, fs: &HdfsFileSystem) -> LineSplitReader {
+        LineSplitReader::new(fs, self.path.clone(), self.start, self.end())
+    }
+}
+
+
+type PathPredicate = Arc<dyn Fn(&Path) -> bool + Send + Sync>;
+
+#[derive(Clone, Debug)]
+enum Input {
+    Path(PathBuf),
+    Glob(String),
+}
+
+// Plans the splits of a set of input files for distributed processing.
+//
+//     let splits = SplitPlanner::new(&fs)
+//         .add_glob("/logs/2026-10-*/part-*")
+//         .target_size(256 * 1024 * 1024)
+//         .plan()?;
+//
+// Directories contribute the files directly inside them (or every file below
+// them with `recursive`), and hidden files (names starting with `.` or `_`)
+// are skipped as MapReduce does. Empty files get no split.
+//
+// Splits start on block boundaries: a target of at least one block is
+// rounded down to whole blocks, while a smaller target cuts every block into
+// equal pieces. Without a target every block is its own split.
+#[derive(Clone)]
+pub struct SplitPlanner {
+    fs: HdfsFileSystem,
+    inputs: Vec<Input>,
+    target_size: Option<u64>,
+    recursive: bool,
+    splittable: Option<PathPredicate>,
+}
+
+impl SplitPlanner {
+
+    pub fn new(fs: &HdfsFileSystem) -> SplitPlanner {
+        SplitPlanner {
+            fs: fs.clone(),
+            inputs: Vec::new(),
+            target_size: None,
+            recursive: false,
+            splittable: None,
+        }
+    }
+
+    pub fn add_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.inputs.push(Input::Path(path.into()));
+        self
+    }
+
+    pub fn add_glob<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.inputs.push(Input::Glob(pattern.into()));
+        self
+    }
+
+    pub fn target_size(mut self, target_size: u64) -> Self {
+        self.target_size = Some(std::cmp::max(target_size, 1));
+        self
+    }
+
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    // Files for which `predicate` returns false (e.g. gzip files) become a
+    // single split each.
+    pub fn splittable<F>(mut self, predicate: F) -> Self
+        where F: Fn(&Path) -> bool + Send + Sync + 'static {
+        self.splittable = Some(Arc::new(predicate));
+        self
+    }
+
+    pub fn plan(&self) -> Result<Vec<InputSplit>> {
+        let mut splits = Vec::new();
+        for (path, metadata) in self.input_files()? {
+            if metadata.is_empty() {
+                continue;
+            }
+
+            let blocks = self.fs.block_locations(&path, ..)?;
+            let ranges = match self.splittable.as_ref().is_none_or(|predicate| predicate(&path)) {
+                true => split_ranges(metadata.len(), metadata.block_size(), self.target_size),
+                false => vec![(0, metadata.len())],
+            };
+
+            for (start, length) in ranges {
+                splits.push(InputSplit {
+                    path: path.clone(),
+                    start,
+                    length,
+                    hosts: rank_hosts(&blocks, start, start + length),
+                });
+            }
+        }
+        Ok(splits)
+    }
+
+    // Every input file once, in the order the inputs were added.
+    fn input_files(&self) -> Result<Vec<(PathBuf, Metadata)>> {
+        let mut entries = Vec::new();
+        for input in &self.inputs {
+            match input {
+                Input::Path(path) => entries.push((path.clone(), self.fs.metadata(path)?)),
+                Input::Glob(pattern) => {
+                    let matches = Glob::new(pattern.as_str()).skip_hidden(true).expand(&self.fs)?;
+                    entries.extend(matches.into_iter().map(|entry| (entry.path().to_path_buf(), entry.metadata().clone())));
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut files = Vec::new();
+        for (path, metadata) in entries {
+            if !metadata.is_dir() {
+                if seen.insert(path.clone()) {
+                    files.push((path, metadata));
+                }
+                continue;
+            }
+
+            let walk = self.fs.walk_dir(&path)
+                .sort(true)
+                .max_depth(if self.recursive { usize::MAX } else { 1 })
+                .filter_entry(|entry| !entry.file_name().starts_with('.') && !entry.file_name().starts_with('_'));
+            for entry in walk {
+                let entry = entry?;
+                if entry.file_type().is_file() && seen.insert(entry.path().to_path_buf()) {
+                    files.push((entry.path().to_path_buf(), entry.metadata().clone()));
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+impl std::fmt::Debug for SplitPlanner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SplitPlanner")
+            .field("inputs", &self.inputs)
+            .field("target_size", &self.target_size)
+            .field("recursive", &self.recursive)
+            .finish()
+    }
+}
+
+// `(start, length)` of each split of a file.
+fn split_ranges(size: u64, block_size: u64, target_size: Option<u64>) -> Vec<(u64, u64)> {
+    let block_size = if block_size > 0 { block_size } else { size };
+    let target_size = target_size.unwrap_or(block_size);
+    let mut ranges = Vec::new();
+
+    if target_size >= block_size {
+        let split_size = target_size / block_size * block_size;
+        let mut start = 0;
+        while (size - start) as f64 / split_size as f64 > SPLIT_SLOP {
+            ranges.push((start, split_size));
+            start += split_size;
+        }
+        ranges.push((start, size - start));
+        return ranges;
+    }
+
+    for block_start in (0..size).step_by(block_size as usize) {
+        let block_length = std::cmp::min(block_size, size - block_start);
+        let pieces = block_length.div_ceil(target_size);
+        let piece_size = block_length.div_ceil(pieces);
+
+        let mut start = block_start;
+        while start < block_start + block_length {
+            let length = std::cmp::min(piece_size, block_start + block_length - start);
+            ranges.push((start, length));
+            start += length;
+        }
+    }
+    ranges
+}
+
+// The hosts of the blocks overlapping `[start, end)`, by the number of bytes
+// of the range they hold.
+fn rank_hosts(blocks: &[BlockLocation], start: u64, end: u64) -> Vec<String> {
+    let mut bytes: HashMap<&str, u64> = HashMap::new();
+    for block in blocks {
+        let overlap_start = std::cmp::max(start, block.offset);
+        let overlap_end = std::cmp::min(end, block.end());
+        if overlap_start < overlap_end {
+            for host in &block.hosts {
+                *bytes.entry(host.as_str()).or_insert(0) += overlap_end - overlap_start;
+            }
+        }
+    }
+
+    let mut hosts: Vec<(&str, u64)> = bytes.into_iter().collect();
+    hosts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    hosts.into_iter().map(|(host, _)| host.to_string()).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use crate::backend::MemoryBackend;
+
+    fn block(offset: u64, length: u64, hosts: &[&str]) -> BlockLocation {
+        BlockLocation { offset, length, hosts: hosts.iter().map(|host| host.to_string()).collect() }
+    }
+
+    #[test]
+    fn ranges_follow_blocks() {
+        assert_eq!(split_ranges(10, 4, None), vec![(0, 4), (4, 4), (8, 2)]);
+        // the tail is folded into the last split when it is small enough
+        assert_eq!(split_ranges(82, 10, Some(25)), vec![(0, 20), (20, 20), (40, 20), (60, 22)]);
+        assert_eq!(split_ranges(83, 10, Some(25)), vec![(0, 20), (20, 20), (40, 20), (60, 20), (80, 3)]);
+        assert_eq!(split_ranges(8, 8, Some(3)), vec![(0, 3), (3, 3), (6, 2)]);
+        assert_eq!(split_ranges(10, 8, Some(4)), vec![(0, 4), (4, 4), (8, 2)]);
+        assert_eq!(split_ranges(5, 0, None), vec![(0, 5)]);
+    }
+
+    #[test]
+    fn hosts_ranked_by_local_bytes() {
+        let blocks = vec![block(0, 4, &["a", "b"]), block(4, 4, &["b", "c"]), block(8, 2, &["c"])];
+
+        assert_eq!(rank_hosts(&blocks, 0, 10), vec!["b", "c", "a"]);
+        assert_eq!(rank_hosts(&blocks, 6, 10), vec!["c", "b"]);
+        assert_eq!(rank_hosts(&blocks, 0, 4), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn splits_read_back_the_whole_file() {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new().with_block_size(4));
+        let content: Vec<u8> = (0..50).collect();
+        fs.create("/in/part-0").unwrap().write_all(&content).unwrap();
+
+        let splits = SplitPlanner::new(&fs).add_path("/in").target_size(8).plan().unwrap();
+        assert_eq!(splits.len(), 7);
+        assert!(splits.iter().all(|split| split.start % 4 == 0 && split.hosts == vec!["localhost"]));
+
+        let mut read_back = Vec::new();
+        for split in &splits {
+            let mut piece = Vec::new();
+            split.open(&fs).read_to_end(&mut piece).unwrap();
+            assert_eq!(piece.len() as u64, split.length);
+            read_back.extend(piece);
+        }
+        assert_eq!(read_back, content);
+    }
+
+    #[test]
+    fn split_readers_stay_in_bounds() {
+        let fs = memory_fs_with(&[("/f", b"0123456789")]);
+
+        let mut reader = HdfsFile::from_split(&fs, "/f", 2, 5);
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "234");
+        assert_eq!(reader.size, 5);
+
+        assert_eq!(reader.seek(SeekFrom::Start(1)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        reader.seek(SeekFrom::Start(3)).unwrap();
+        content.clear();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "34");
+
+        // a split past the end of the file stops at the file
+        content.clear();
+        HdfsFile::from_split(&fs, "/f", 8, 20).read_to_string(&mut content).unwrap();
+        assert_eq!(content, "89");
+    }
+
+    fn memory_fs_with(files: &[(&str, &[u8])]) -> HdfsFileSystem {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new().with_block_size(4));
+        for (path, content) in files {
+            fs.create(*path).unwrap().write_all(content).unwrap();
+        }
+        fs
+    }
+
+    #[test]
+    fn inputs_from_globs_and_directories() {
+        let fs = memory_fs_with(&[
+            ("/logs/a/part-0.gz", b"0123456789"),
+            ("/logs/a/_SUCCESS", b""),
+            ("/logs/a/.part-0.gz.crc", b"crc"),
+            ("/logs/b/part-0.gz", b"0123"),
+            ("/logs/b/empty", b""),
+            ("/logs/b/nested/part-1", b"0123"),
+        ]);
+
+        let planner = SplitPlanner::new(&fs)
+            .add_glob("/logs/*")
+            .add_path("/logs/a/part-0.gz")
+            .splittable(|path| path.extension().is_none_or(|extension| extension != "gz"));
+        let splits = planner.clone().plan().unwrap();
+        let files: Vec<_> = splits.iter().map(|split| (split.path.to_str().unwrap(), split.length)).collect();
+        assert_eq!(files, vec![("/logs/a/part-0.gz", 10), ("/logs/b/part-0.gz", 4)]);
+
+        let nested = planner.recursive(true).plan().unwrap();
+        assert_eq!(nested.len(), 3);
+        assert_eq!(nested[1].path, PathBuf::from("/logs/b/nested/part-1"));
+
+        assert!(SplitPlanner::new(&fs).add_path("/missing").plan().is_err());
+    }
+}
@@ -0,0 +1,37 @@
+// Where the bytes read through one open file came from, as counted by the
+// HDFS client (`hdfsFileGetReadStatistics`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ReadStatistics {
+    pub total_bytes: u64,
+    // read from a datanode on the same host, including short-circuit reads
+    pub local_bytes: u64,
+    // read straight from the block files, bypassing the datanode
+    pub short_circuit_bytes: u64,
+    // read through zero-copy (mmapped) reads
+    pub zero_copy_bytes: u64,
+    // read from datanodes on other hosts
+    pub remote_bytes: u64,
+}
+
+impl ReadStatistics {
+
+    // The share of the bytes read locally, from 0 to 1; 1 before anything
+    // was read.
+    pub fn locality(&self) -> f64 {
+        match self.total_bytes {
+            0 => 1.0,
+            total_bytes => self.local_bytes as f64 / total_bytes as f64,
+        }
+    }
+}
+
+// Hedged reads (a second read from another datanode when the first one is
+// slow) of all the filesystems of the process (`hdfsGetHedgedReadMetrics`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HedgedReadMetrics {
+    pub ops: u64,
+    // hedged reads that finished before the original read
+    pub ops_won: u64,
+    // hedged reads run in the reading thread because the pool was busy
+    pub ops_in_current_thread: u64,
+}
@@ -0,0 +1,126 @@
+use crate::*;
+use crate::error::Result;
+
+
+// The parts of a filesystem URI such as `hdfs://etl@nn:8020/user/etl` that
+// pick the filesystem; the path is left to the caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FsUri {
+    // `None` for bare paths and `hdfs:///...`, which use the default namenode
+    scheme: Option<String>,
+    user: Option<String>,
+    host: String,
+    port: Option<u16>,
+}
+
+impl FsUri {
+
+    pub(crate) fn parse(uri: &HdfsPath) -> Result<FsUri> {
+        let invalid = |reason: &str| HdfsError::new(HdfsErrorKind::InvalidInput,
+            format!("Invalid filesystem URI {:?}: {}", uri.as_str(), reason));
+
+        let authority = uri.authority().unwrap_or("");
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some(("", _)) => return Err(invalid("empty user")),
+            Some((user, host_port)) => (Some(user.to_string()), host_port),
+            None => (None, authority),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port.parse::<u16>().map_err(|_| invalid("bad port"))?)),
+            None => (host_port, None),
+        };
+
+        let scheme = uri.scheme().map(|scheme| scheme.to_ascii_lowercase());
+        match scheme.as_deref() {
+            Some("file") if !matches!(host, "" | "localhost") || user.is_some() || port.is_some() =>
+                Err(invalid("only local files can be opened")),
+            Some("file") => Ok(FsUri { scheme, user: None, host: String::new(), port: None }),
+            None if !authority.is_empty() => Err(invalid("missing scheme")),
+            _ if host.is_empty() && (user.is_some() || port.is_some()) => Err(invalid("missing host")),
+            // Hadoop takes `hdfs:///path` from `fs.defaultFS`
+            _ if host.is_empty() => Ok(FsUri { scheme: None, user: None, host: String::new(), port: None }),
+            _ => Ok(FsUri { scheme, user, host: host.to_ascii_lowercase(), port }),
+        }
+    }
+
+    fn name_node(&self) -> String {
+        match (&self.scheme, self.port) {
+            (Some(scheme), Some(port)) => format!("{}://{}:{}", scheme, self.host, port),
+            (Some(scheme), None) => format!("{}://{}", scheme, self.host),
+            (None, _) => String::from("default"),
+        }
+    }
+
+    fn builder(&self) -> HdfsFileSystemBuilder {
+        let builder = HdfsFileSystem::builder().name_node(self.name_node());
+        match &self.user {
+            Some(user) => builder.user(user.as_str()),
+            None => builder,
+        }
+    }
+
+    // The cached filesystem, connecting on first use.
+    pub(crate) fn connect(&self) -> Result<HdfsFileSystem> {
+        crate::cache::shared().get(&self.builder())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use crate::backend::local::TempRoot;
+
+    fn parse(uri: &str) -> Result<FsUri> {
+        FsUri::parse(&HdfsPath::new(uri).unwrap())
+    }
+
+    #[test]
+    fn parses_authorities() {
+        let uri = parse("HDFS://etl@NN.example.com:8020/user/etl").unwrap();
+        assert_eq!(uri, FsUri {
+            scheme: Some(String::from("hdfs")),
+            user: Some(String::from("etl")),
+            host: String::from("nn.example.com"),
+            port: Some(8020),
+        });
+        assert_eq!(uri.name_node(), "hdfs://nn.example.com:8020");
+
+        assert_eq!(parse("viewfs://cluster/data").unwrap().name_node(), "viewfs://cluster");
+        assert_eq!(parse("file:///tmp/x").unwrap().name_node(), "file://");
+        assert_eq!(parse("file:/tmp/x").unwrap().name_node(), "file://");
+        for default in &["/user/etl", "data", "hdfs:///user/etl"] {
+            assert_eq!(parse(default).unwrap().name_node(), "default", "{}", default);
+        }
+
+        for invalid in &["hdfs://nn:port/", "hdfs://nn:70000/", "hdfs://@nn/", "hdfs://:8020/", "hdfs://etl@/user",
+                "file://otherhost/tmp", "file://etl@localhost/tmp", "//nn/tmp"] {
+            assert_eq!(parse(invalid).unwrap_err().kind(), HdfsErrorKind::InvalidInput, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn file_uris_use_the_local_filesystem() {
+        let root = TempRoot::new();
+        let dir = &root.0;
+        let uri = format!("file://{}/f", dir.display());
+
+        let fs = HdfsFileSystem::from_uri(&uri).unwrap();
+        fs.create(dir.join("f")).unwrap().write_all(b"local").unwrap();
+        assert_eq!(std::fs::read(dir.join("f")).unwrap(), b"local");
+
+        let mut content = String::new();
+        HdfsFile::open_uri(&uri).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "local");
+        assert_eq!(HdfsFile::open_uri(format!("{}-missing", uri)).err().unwrap().kind(), HdfsErrorKind::NotFound);
+
+        // one handle per authority, so they share the working directory; a
+        // private cache keeps the shared handle's working directory intact
+        let cache = FileSystemCache::new();
+        let fs = cache.get(&parse(&uri).unwrap().builder()).unwrap();
+        fs.set_working_directory(dir).unwrap();
+        let same_fs = cache.get(&parse("file://localhost/").unwrap().builder()).unwrap();
+        assert_eq!(same_fs.working_directory().unwrap().as_str(), dir.to_str().unwrap());
+    }
+}
@@ -0,0 +1,437 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::thread::JoinHandle;
+
+use crate::*;
+use crate::error::Result;
+
+
+type Predicate = Arc<dyn Fn(&DirEntry) -> bool + Send + Sync>;
+
+
+// A recursive directory walk, modelled after the `walkdir` crate.
+//
+//     let partitions = fs.walk_dir("/warehouse/table")
+//         .max_depth(2)
+//         .filter_entry(|entry| !entry.file_name().starts_with('_'))
+//         .parallelism(8);
+//     for entry in partitions {
+//         println!("{}", entry?.path().display());
+//     }
+//
+// Entries come out in pre-order: every directory before its contents (a
+// parallel walk interleaves directories; see `parallelism`). The root itself
+// is not yielded and must be a directory. A directory that fails to list
+// yields one error and the walk goes on with its siblings.
+#[derive(Clone)]
+pub struct WalkDir {
+    fs: HdfsFileSystem,
+    root: PathBuf,
+    max_depth: usize,
+    sort: bool,
+    parallelism: usize,
+    filter_entry: Option<Predicate>,
+    descend_into: Option<Predicate>,
+}
+
+impl WalkDir {
+
+    pub fn new<P: Into<PathBuf>>(fs: &HdfsFileSystem, root: P) -> WalkDir {
+        WalkDir {
+            fs: fs.clone(),
+            root: root.into(),
+            max_depth: usize::MAX,
+            sort: false,
+            parallelism: 1,
+            filter_entry: None,
+            descend_into: None,
+        }
+    }
+
+    // Deepest entries to yield; the children of the root are at depth 1.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // Yields the entries of each directory ordered by file name instead of
+    // in listing order.
+    pub fn sort(mut self, sort: bool) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    // Skips entries for which `predicate` returns false; skipped directories
+    // are not descended into either.
+    pub fn filter_entry<F>(mut self, predicate: F) -> Self
+        where F: Fn(&DirEntry) -> bool + Send + Sync + 'static {
+        self.filter_entry = Some(Arc::new(predicate));
+        self
+    }
+
+    // Yields directories for which `predicate` returns false without
+    // descending into them.
+    pub fn descend_into<F>(mut self, predicate: F) -> Self
+        where F: Fn(&DirEntry) -> bool + Send + Sync + 'static {
+        self.descend_into = Some(Arc::new(predicate));
+        self
+    }
+
+    // Lists up to `threads` directories at once over the shared filesystem
+    // handle. Entries are then yielded as directories are listed: each
+    // directory still comes before its contents and `sort` still orders the
+    // entries of each directory, but directories are interleaved. Workers
+    // wait while `BUFFERED_ENTRIES` entries are waiting to be yielded.
+    pub fn parallelism(mut self, threads: usize) -> Self {
+        self.parallelism = std::cmp::max(threads, 1);
+        self
+    }
+
+    fn list(&self, dir: &Path, depth: usize) -> Vec<Result<DirEntry>> {
+        let read_dir = match self.fs.read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) => return vec![Err(err)],
+        };
+
+        let mut entries: Vec<_> = read_dir
+            .map(|entry| entry.map(|mut entry| { entry.depth = depth; entry }))
+            .filter(|entry| match (entry, &self.filter_entry) {
+                (Ok(entry), Some(predicate)) => predicate(entry),
+                _ => true,
+            })
+            .collect();
+
+        if self.sort {
+            entries.sort_by(|a, b| sort_name(a).cmp(&sort_name(b)));
+        }
+        entries
+    }
+
+    fn descends(&self, entry: &DirEntry) -> bool {
+        entry.file_type().is_dir() && entry.depth() < self.max_depth
+            && self.descend_into.as_ref().is_none_or(|predicate| predicate(entry))
+    }
+
+    fn spawn_workers(&self) -> Workers {
+        let (sender, entries) = std::sync::mpsc::sync_channel(BUFFERED_ENTRIES);
+        let queue = Arc::new(WorkQueue {
+            queue: Mutex::new(Queue { dirs: vec![(self.root.clone(), 1)], busy: 0, stopped: false }),
+            changed: Condvar::new(),
+        });
+
+        let threads = (0..self.parallelism).map(|_| {
+            let (walk, queue, sender) = (self.clone(), queue.clone(), sender.clone());
+            std::thread::spawn(move || walk.work(&queue, &sender))
+        }).collect();
+        Workers { entries, threads }
+    }
+
+    // Lists directories from `queue` until the walk is over or the iterator
+    // is dropped.
+    fn work(&self, queue: &WorkQueue, sender: &SyncSender<Result<DirEntry>>) {
+        while let Some((dir, depth)) = queue.take() {
+            let mut done = Done { queue, subdirs: Vec::new() };
+            for entry in self.list(&dir, depth) {
+                if let Ok(entry) = &entry {
+                    if self.descends(entry) {
+                        done.subdirs.push((entry.path().to_path_buf(), depth + 1));
+                    }
+                }
+                // subdirectories are only queued once their entries are sent
+                if sender.send(entry).is_err() {
+                    done.subdirs.clear();
+                    queue.stop();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// How many entries a parallel walk lists ahead of the caller.
+const BUFFERED_ENTRIES: usize = 1024;
+
+struct WorkQueue {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+struct Queue {
+    dirs: Vec<(PathBuf, usize)>,
+    busy: usize,
+    stopped: bool,
+}
+
+impl WorkQueue {
+
+    // The next directory to list, or `None` once nothing is queued and
+    // nobody is left to queue more.
+    fn take(&self) -> Option<(PathBuf, usize)> {
+        let mut queue = self.lock();
+        loop {
+            if queue.stopped {
+                return None;
+            }
+            if let Some(next) = queue.dirs.pop() {
+                queue.busy += 1;
+                return Some(next);
+            }
+            if queue.busy == 0 {
+                return None;
+            }
+            queue = self.changed.wait(queue).unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    fn stop(&self) {
+        self.lock().stopped = true;
+        self.changed.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+// Hands back a directory taken from the queue, even when listing it panics;
+// otherwise the other workers would wait for it forever instead of finishing
+// so the panic can be propagated.
+struct Done<'a> {
+    queue: &'a WorkQueue,
+    subdirs: Vec<(PathBuf, usize)>,
+}
+
+impl Drop for Done<'_> {
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock();
+        queue.dirs.append(&mut self.subdirs);
+        queue.busy -= 1;
+        self.queue.changed.notify_all();
+    }
+}
+
+struct Workers {
+    entries: Receiver<Result<DirEntry>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Workers {
+
+    fn next(&mut self) -> Option<Result<DirEntry>> {
+        match self.entries.recv() {
+            Ok(entry) => Some(entry),
+            // every worker is done; rethrow the panic of any that failed
+            Err(_) => {
+                for thread in self.threads.drain(..) {
+                    if let Err(panic) = thread.join() {
+                        std::panic::resume_unwind(panic);
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+// Errors sort first.
+fn sort_name(entry: &Result<DirEntry>) -> Option<&str> {
+    entry.as_ref().ok().map(DirEntry::file_name)
+}
+
+impl IntoIterator for WalkDir {
+    type Item = Result<DirEntry>;
+    type IntoIter = WalkDirIter;
+
+    fn into_iter(self) -> WalkDirIter {
+        let mut stack = Vec::new();
+        let mut workers = None;
+        match self.max_depth > 0 {
+            true if self.parallelism > 1 => workers = Some(self.spawn_workers()),
+            true => stack.push(self.list(&self.root, 1).into_iter()),
+            false => {}
+        }
+
+        WalkDirIter { walk: self, stack, workers }
+    }
+}
+
+impl std::fmt::Debug for WalkDir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WalkDir")
+            .field("root", &self.root)
+            .field("max_depth", &self.max_depth)
+            .field("sort", &self.sort)
+            .field("parallelism", &self.parallelism)
+            .finish()
+    }
+}
+
+
+pub struct WalkDirIter {
+    walk: WalkDir,
+    // the remaining entries of each directory being walked
+    stack: Vec<std::vec::IntoIter<Result<DirEntry>>>,
+    // set for parallel walks, which do not use the stack
+    workers: Option<Workers>,
+}
+
+impl Iterator for WalkDirIter {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Result<DirEntry>> {
+        if let Some(workers) = &mut self.workers {
+            return workers.next();
+        }
+
+        loop {
+            let entry = match self.stack.last_mut()?.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            if self.walk.descends(&entry) {
+                self.stack.push(self.walk.list(entry.path(), entry.depth() + 1).into_iter());
+            }
+            return Some(Ok(entry));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    fn partitioned_fs() -> HdfsFileSystem {
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new());
+        for day in &["dt=2020-01-02", "dt=2020-01-01"] {
+            for hour in &["hour=01", "hour=00"] {
+                fs.create(format!("/warehouse/t/{}/{}/part-0", day, hour)).unwrap();
+            }
+        }
+        fs.create("/warehouse/t/_SUCCESS").unwrap();
+        fs
+    }
+
+    fn paths(walk: WalkDir) -> Vec<String> {
+        walk.into_iter().map(|entry| entry.unwrap().path().to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn walks_in_pre_order() {
+        let fs = partitioned_fs();
+        let walk = fs.walk_dir("/warehouse/t").sort(true).max_depth(2);
+
+        assert_eq!(paths(walk), vec![
+            "/warehouse/t/_SUCCESS",
+            "/warehouse/t/dt=2020-01-01",
+            "/warehouse/t/dt=2020-01-01/hour=00",
+            "/warehouse/t/dt=2020-01-01/hour=01",
+            "/warehouse/t/dt=2020-01-02",
+            "/warehouse/t/dt=2020-01-02/hour=00",
+            "/warehouse/t/dt=2020-01-02/hour=01",
+        ]);
+
+        let depths: Vec<_> = fs.walk_dir("/warehouse").into_iter().map(|entry| entry.unwrap().depth()).collect();
+        assert_eq!(depths.iter().max(), Some(&4));
+        assert_eq!(fs.walk_dir("/warehouse").max_depth(0).into_iter().count(), 0);
+    }
+
+    #[test]
+    fn filters_and_prunes() {
+        let fs = partitioned_fs();
+
+        let walk = fs.walk_dir("/warehouse/t").sort(true)
+            .filter_entry(|entry| !entry.file_name().starts_with('_'))
+            .descend_into(|entry| entry.file_name() != "dt=2020-01-02");
+        assert_eq!(paths(walk), vec![
+            "/warehouse/t/dt=2020-01-01",
+            "/warehouse/t/dt=2020-01-01/hour=00",
+            "/warehouse/t/dt=2020-01-01/hour=00/part-0",
+            "/warehouse/t/dt=2020-01-01/hour=01",
+            "/warehouse/t/dt=2020-01-01/hour=01/part-0",
+            "/warehouse/t/dt=2020-01-02",
+        ]);
+    }
+
+    #[test]
+    fn parallel_walk_matches_sequential() {
+        let fs = partitioned_fs();
+        for day in 0..20 {
+            fs.create(format!("/warehouse/t/dt=2021-01-{:02}/part-0", day)).unwrap();
+        }
+
+        let sequential = paths(fs.walk_dir("/warehouse").sort(true));
+        let parallel = paths(fs.walk_dir("/warehouse").sort(true).parallelism(4));
+        assert_eq!(sequential.len(), 1 + 23 + 4 + 4 + 20);
+        // directories are interleaved, but each still precedes its contents
+        for (i, path) in parallel.iter().enumerate() {
+            let parent = Path::new(path).parent().unwrap();
+            assert!(parent == Path::new("/warehouse") || parallel[..i].iter().any(|other| Path::new(other) == parent), "{}", path);
+        }
+        let mut parallel = parallel;
+        parallel.sort();
+        assert_eq!(parallel, sequential);
+
+        let mut shallow = paths(fs.walk_dir("/warehouse").sort(true).max_depth(2).parallelism(4));
+        shallow.sort();
+        assert_eq!(shallow, paths(fs.walk_dir("/warehouse").sort(true).max_depth(2)));
+    }
+
+    #[test]
+    fn parallel_walk_lists_a_bounded_amount_ahead() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let fs = HdfsFileSystem::with_backend("memory", MemoryBackend::new());
+        for dir in 0..40 {
+            for file in 0..100 {
+                fs.create(format!("/t/{}/{}", dir, file)).unwrap();
+            }
+        }
+
+        let listed = Arc::new(AtomicUsize::new(0));
+        let counter = listed.clone();
+        let mut walk = fs.walk_dir("/t").parallelism(4)
+            .filter_entry(move |_| { counter.fetch_add(1, Ordering::SeqCst); true })
+            .into_iter();
+        walk.next().unwrap().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(listed.load(Ordering::SeqCst) < 40 + 40 * 100, "{}", listed.load(Ordering::SeqCst));
+        assert_eq!(walk.count(), 40 + 40 * 100 - 1);
+    }
+
+    #[test]
+    fn parallel_walk_propagates_panics() {
+        let fs = partitioned_fs();
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || {
+            let walk = fs.walk_dir("/warehouse").parallelism(4)
+                .descend_into(|entry| entry.file_name() != "hour=00" || panic!("predicate failed"));
+            sender.send(walk.into_iter().count()).unwrap();
+        });
+
+        // the panic drops the sender; a hang would time out instead
+        let result = receiver.recv_timeout(std::time::Duration::from_secs(30));
+        assert_eq!(result, Err(std::sync::mpsc::RecvTimeoutError::Disconnected));
+    }
+
+    #[test]
+    fn listing_errors_are_yielded() {
+        let fs = partitioned_fs();
+
+        let mut walk = fs.walk_dir("/missing").into_iter();
+        assert_eq!(walk.next().unwrap().unwrap_err().kind(), HdfsErrorKind::NotFound);
+        assert!(walk.next().is_none());
+
+        let mut walk = fs.walk_dir("/warehouse/t/_SUCCESS").parallelism(2).into_iter();
+        assert_eq!(walk.next().unwrap().unwrap_err().kind(), HdfsErrorKind::NotADirectory);
+        assert!(walk.next().is_none());
+    }
+}
@@ -0,0 +1,288 @@
+use std::ops::Deref;
+
+use crate::*;
+use crate::error::Result;
+use crate::hdfs_fs::to_cstring;
+
+
+// Reads a file through libhdfs' zero-copy API (`hadoopReadZero`): when the
+// client can read a block locally, buffers are mmapped straight from the
+// datanode's block file instead of being copied.
+//
+//     let mut reader = ZeroCopyReader::new(fs.open("/data/part-0")?)
+//         .skip_checksum(true)
+//         .byte_buffer_pool(ZeroCopyReader::ELASTIC_BYTE_BUFFER_POOL);
+//     while let Some(buffer) = reader.read(8 * 1024 * 1024)? {
+//         process(&buffer);
+//     }
+//
+// mmapped reads need short-circuit local reads and either `skip_checksum` or
+// a block cached by the datanode. Otherwise libhdfs copies into a buffer
+// from the byte buffer pool, and without a pool the read fails with
+// `Unsupported`. Files of other backends are read by copying into a buffer
+// owned by the reader.
+//
+// Reads start at the file's position and move it; like `Read`, they stop at
+// the end of a `from_split` range.
+pub struct ZeroCopyReader {
+    file: HdfsFile,
+    skip_checksum: bool,
+    byte_buffer_pool: Option<String>,
+    // allocated on the first zero-copy read
+    options: Option<RzOptions>,
+    // for backends without zero-copy reads
+    copy_buffer: Vec<u8>,
+}
+
+struct RzOptions(*mut hadoopRzOptions);
+
+// The options are a plain C struct (and a global reference to the pool),
+// not tied to the thread that created them.
+unsafe impl Send for RzOptions {}
+
+impl Drop for RzOptions {
+    fn drop(&mut self) {
+        unsafe { hadoopRzOptionsFree(self.0); }
+    }
+}
+
+impl ZeroCopyReader {
+
+    // The pool shipped with Hadoop; libhdfs wants the JNI class name.
+    pub const ELASTIC_BYTE_BUFFER_POOL: &'static str = "org/apache/hadoop/io/ElasticByteBufferPool";
+
+    pub fn new(file: HdfsFile) -> ZeroCopyReader {
+        ZeroCopyReader {
+            file,
+            skip_checksum: false,
+            byte_buffer_pool: None,
+            options: None,
+            copy_buffer: Vec::new(),
+        }
+    }
+
+    // Lets libhdfs skip checksum verification, which mmapped reads of
+    // uncached blocks require.
+    pub fn skip_checksum(mut self, skip_checksum: bool) -> Self {
+        self.skip_checksum = skip_checksum;
+        self
+    }
+
+    // The `ByteBufferPool` class to copy into when a read cannot be
+    // zero-copy, e.g. `ELASTIC_BYTE_BUFFER_POOL`.
+    pub fn byte_buffer_pool<S: Into<String>>(mut self, class_name: S) -> Self {
+        self.byte_buffer_pool = Some(class_name.into());
+        self
+    }
+
+    pub fn file(&self) -> &HdfsFile {
+        &self.file
+    }
+
+    pub fn into_inner(self) -> HdfsFile {
+        self.file
+    }
+
+    // Reads up to `max_length` bytes, often fewer (zero-copy reads never
+    // cross a block boundary); `None` at the end of the file. The reader
+    // stays borrowed until the buffer is dropped.
+    pub fn read(&mut self, max_length: usize) -> Result<Option<ZeroCopyBuffer<'_>>> {
+        let native = self.file.read_stream()?.raw_file().is_some();
+
+        let remaining = std::cmp::max(self.file.size - self.file.read_pos, 0) as u64;
+        let length = std::cmp::min(std::cmp::min(max_length as u64, remaining), i32::MAX as u64) as usize;
+        if length == 0 {
+            return Ok(None);
+        }
+
+        let position = self.file.read_pos;
+        let options = match native {
+            true => self.native_options()?,
+            false => std::ptr::null_mut(),
+        };
+
+        let stream = self.file.read_stream()?;
+        let raw_file = match stream.raw_file() {
+            Some(raw_file) => raw_file,
+            None => {
+                self.copy_buffer.resize(std::cmp::max(self.copy_buffer.len(), length), 0);
+                let read_bytes = std::cmp::min(stream.pread(position, &mut self.copy_buffer[..length])?, length);
+                if read_bytes == 0 {
+                    return Ok(None);
+                }
+
+                self.file.read_pos += read_bytes as i64;
+                return Ok(Some(ZeroCopyBuffer { data: &self.copy_buffer[..read_bytes], native: None }));
+            }
+        };
+
+        // `read` does positioned reads, so the stream may be elsewhere
+        stream.seek(position)?;
+        let buffer = unsafe { hadoopReadZero(raw_file, options, length as i32) };
+        if buffer.is_null() {
+            return Err(HdfsError::last_native(format!("Zero-copy read of {:?} failed", self.file.path)));
+        }
+
+        let (data, read_bytes) = unsafe { (hadoopRzBufferGet(buffer) as *const u8, hadoopRzBufferLength(buffer)) };
+        if data.is_null() || read_bytes <= 0 {
+            unsafe { hadoopRzBufferFree(raw_file, buffer); }
+            return Ok(None);
+        }
+
+        self.file.read_pos += read_bytes as i64;
+        // valid until `hadoopRzBufferFree`, which only the guard calls
+        let data = unsafe { std::slice::from_raw_parts(data, read_bytes as usize) };
+        Ok(Some(ZeroCopyBuffer { data, native: Some((raw_file, buffer)) }))
+    }
+
+    fn native_options(&mut self) -> Result<*mut hadoopRzOptions> {
+        if let Some(options) = &self.options {
+            return Ok(options.0);
+        }
+
+        let options = unsafe { hadoopRzOptionsAlloc() };
+        if options.is_null() {
+            return Err(HdfsError::last_native("Failed to allocate zero-copy options"));
+        }
+        let options = RzOptions(options);
+
+        if unsafe { hadoopRzOptionsSetSkipChecksum(options.0, self.skip_checksum as i32) } != 0 {
+            return Err(HdfsError::last_native("Failed to set zero-copy checksum skipping"));
+        }
+        if let Some(class_name) = &self.byte_buffer_pool {
+            let class = to_cstring(class_name)?;
+            if unsafe { hadoopRzOptionsSetByteBufferPool(options.0, class.as_ptr()) } != 0 {
+                return Err(HdfsError::last_native(format!("Failed to use byte buffer pool {:?}", class_name)));
+            }
+        }
+
+        let raw = options.0;
+        self.options = Some(options);
+        Ok(raw)
+    }
+}
+
+impl std::fmt::Debug for ZeroCopyReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZeroCopyReader")
+            .field("path", &self.file.path)
+            .field("read_pos", &self.file.read_pos)
+            .field("skip_checksum", &self.skip_checksum)
+            .field("byte_buffer_pool", &self.byte_buffer_pool)
+            .finish()
+    }
+}
+
+
+// The bytes of one `ZeroCopyReader::read`; a native buffer is handed back
+// to libhdfs when this is dropped.
+pub struct ZeroCopyBuffer<'a> {
+    data: &'a [u8],
+    // the buffer and the stream it was read from
+    native: Option<(hdfsFile, *mut hadoopRzBuffer)>,
+}
+
+impl ZeroCopyBuffer<'_> {
+
+    // Whether the bytes came from libhdfs rather than a copying read.
+    pub fn is_native(&self) -> bool {
+        self.native.is_some()
+    }
+}
+
+impl Deref for ZeroCopyBuffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.data
+    }
+}
+
+impl AsRef<[u8]> for ZeroCopyBuffer<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.data
+    }
+}
+
+impl Drop for ZeroCopyBuffer<'_> {
+    fn drop(&mut self) {
+        if let Some((file, buffer)) = self.native.take() {
+            unsafe { hadoopRzBufferFree(file, buffer); }
+        }
+    }
+}
+
+impl std::fmt::Debug for ZeroCopyBuffer<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZeroCopyBuffer")
+            .field("len", &self.data.len())
+            .field("native", &self.is_native())
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom, Write};
+    use crate::backend::memory::fs_with;
+
+    #[test]
+    fn copies_without_libhdfs() {
+        let fs = fs_with(b"0123456789");
+        let mut file = fs.open("/f").unwrap();
+        file.seek(SeekFrom::Start(2)).unwrap();
+
+        let mut reader = ZeroCopyReader::new(file).skip_checksum(true);
+        let mut chunks = Vec::new();
+        while let Some(buffer) = reader.read(3).unwrap() {
+            assert!(!buffer.is_native());
+            chunks.push(String::from_utf8(buffer.to_vec()).unwrap());
+        }
+        assert_eq!(chunks, vec!["234", "567", "89"]);
+        assert_eq!(reader.into_inner().read_pos, 10);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_a_split() {
+        let fs = fs_with(b"0123456789");
+
+        let mut reader = ZeroCopyReader::new(HdfsFile::from_split(&fs, "/f", 3, 6));
+        assert_eq!(&*reader.read(100).unwrap().unwrap(), b"345");
+        assert!(reader.read(100).unwrap().is_none());
+        assert!(reader.read(0).unwrap().is_none());
+    }
+
+    #[test]
+    fn writers_cannot_be_read() {
+        let fs = fs_with(b"");
+        let mut writer = fs.create("/g").unwrap();
+        writer.write_all(b"abc").unwrap();
+
+        let err = ZeroCopyReader::new(writer).read(10).unwrap_err();
+        assert_eq!(err.kind(), HdfsErrorKind::Unsupported);
+
+        let err = ZeroCopyReader::new(HdfsFile::from_split(&fs, "/missing", 0, 10)).read(10).unwrap_err();
+        assert_eq!(err.kind(), HdfsErrorKind::NotFound);
+    }
+
+    #[test]
+    #[ignore = "requires a live HDFS cluster"]
+    fn reads_through_libhdfs() {
+        let fs = HdfsFileSystem::connect("default").unwrap();
+        let content: Vec<u8> = (0..=255).cycle().take(1 << 20).collect();
+        fs.create("/tmp/zero_copy").unwrap().write_all(&content).unwrap();
+
+        let mut reader = ZeroCopyReader::new(fs.open("/tmp/zero_copy").unwrap())
+            .skip_checksum(true)
+            .byte_buffer_pool(ZeroCopyReader::ELASTIC_BYTE_BUFFER_POOL);
+        let mut read_back = Vec::new();
+        while let Some(buffer) = reader.read(64 * 1024).unwrap() {
+            assert!(buffer.is_native());
+            read_back.extend_from_slice(&buffer);
+        }
+        assert_eq!(read_back, content);
+        fs.remove_file("/tmp/zero_copy").unwrap();
+    }
+}